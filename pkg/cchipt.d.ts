@@ -0,0 +1,45 @@
+/* tslint:disable */
+/* eslint-disable */
+/**
+*/
+export class Chip8 {
+  free(): void;
+/**
+*/
+  constructor();
+/**
+* @param {Uint8Array} bytes
+*/
+  load_rom(bytes: Uint8Array): void;
+/**
+*/
+  tick(): void;
+/**
+*/
+  tick_timers(): void;
+/**
+* @param {number} key
+* @param {boolean} pressed
+*/
+  set_key(key: number, pressed: boolean): void;
+/**
+* @returns {Uint8Array}
+*/
+  get_display(): Uint8Array;
+/**
+* @returns {number}
+*/
+  display_width(): number;
+/**
+* @returns {number}
+*/
+  display_height(): number;
+/**
+* @returns {boolean}
+*/
+  sound_active(): boolean;
+/**
+* @returns {boolean}
+*/
+  make_beep(): boolean;
+}
@@ -0,0 +1,196 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rodio::{source::SineWave, OutputStream, Sink, Source};
+
+pub const TONE_FREQUENCY: f32 = 440.0;
+const TONE_VOLUME: f32 = 0.2;
+const SAMPLE_RATE: u32 = 44100;
+
+// XO-CHIP sound: 128 1-bit samples, looped at a rate set by the pitch register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub buffer: [u8; 16],
+    pub pitch: u8,
+}
+
+impl AudioPattern {
+    // Samples per second, 4000 Hz at the default pitch of 64
+    pub fn playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    pub fn sample(&self, index: usize) -> bool {
+        let index = index % 128;
+        self.buffer[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+}
+
+// Plays whatever pattern is in the shared slot, so the CPU can change it while it plays
+struct PatternWave {
+    pattern: Arc<Mutex<AudioPattern>>,
+    position: f32, // In pattern samples
+}
+
+impl Iterator for PatternWave {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let pattern = *self.pattern.lock().unwrap();
+        let sample = match pattern.sample(self.position as usize) {
+            true => TONE_VOLUME,
+            false => -TONE_VOLUME,
+        };
+        self.position = (self.position + pattern.playback_rate() / SAMPLE_RATE as f32) % 128.0;
+        Some(sample)
+    }
+}
+
+impl Source for PatternWave {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+struct Output {
+    _stream: OutputStream, // Has to be kept alive for the sinks to make any sound
+    tone: Sink,
+    pattern: Sink,
+}
+
+pub struct Sound {
+    output: Option<Output>,
+    active: bool,
+    pattern: Option<AudioPattern>, // Played instead of the tone when set
+    shared_pattern: Arc<Mutex<AudioPattern>>,
+    volume: f32, // Gain on top of the fixed tone volume, from 0 to 1
+}
+
+impl Sound {
+    pub fn new() -> Self {
+        let shared_pattern = Arc::new(Mutex::new(AudioPattern {
+            buffer: [0; 16],
+            pitch: 64,
+        }));
+
+        // Without an audio device the emulator keeps running, just silently
+        let output = match OutputStream::try_default() {
+            Ok((stream, handle)) => match (Sink::try_new(&handle), Sink::try_new(&handle)) {
+                (Ok(tone), Ok(pattern)) => {
+                    tone.pause();
+                    tone.append(SineWave::new(TONE_FREQUENCY).amplify(TONE_VOLUME));
+                    pattern.pause();
+                    pattern.append(PatternWave {
+                        pattern: Arc::clone(&shared_pattern),
+                        position: 0.0,
+                    });
+                    Some(Output {
+                        _stream: stream,
+                        tone,
+                        pattern,
+                    })
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("Failed to create audio sink: {}", e);
+                    None
+                }
+            },
+            Err(e) => {
+                eprintln!("Failed to open audio output: {}", e);
+                None
+            }
+        };
+
+        Self {
+            output,
+            active: false,
+            pattern: None,
+            shared_pattern,
+            volume: 1.0,
+        }
+    }
+
+    // Never opens an audio device
+    pub fn silent() -> Self {
+        Self {
+            output: None,
+            active: false,
+            pattern: None,
+            shared_pattern: Arc::new(Mutex::new(AudioPattern {
+                buffer: [0; 16],
+                pitch: 64,
+            })),
+            volume: 1.0,
+        }
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        if active == self.active {
+            return;
+        }
+        self.active = active;
+        self.update_sinks();
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        if volume == self.volume {
+            return;
+        }
+        self.volume = volume;
+        if let Some(output) = &self.output {
+            output.tone.set_volume(volume);
+            output.pattern.set_volume(volume);
+        }
+    }
+
+    // `None` goes back to the plain CHIP-8 tone
+    pub fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        if pattern == self.pattern {
+            return;
+        }
+        if let Some(pattern) = pattern {
+            *self.shared_pattern.lock().unwrap() = pattern;
+        }
+        let switched = pattern.is_some() != self.pattern.is_some();
+        self.pattern = pattern;
+        if switched {
+            self.update_sinks();
+        }
+    }
+
+    fn update_sinks(&self) {
+        if let Some(output) = &self.output {
+            let (playing, paused) = match self.pattern {
+                Some(_) => (&output.pattern, &output.tone),
+                None => (&output.tone, &output.pattern),
+            };
+            paused.pause();
+            if self.active {
+                playing.play();
+            } else {
+                playing.pause();
+            }
+        }
+    }
+}
+
+impl Default for Sound {
+    fn default() -> Self {
+        Self::new()
+    }
+}
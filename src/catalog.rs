@@ -0,0 +1,193 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::OnceLock};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::chip8::QuirksPreset;
+
+pub const SUBMIT_METADATA_URL: &str = "https://github.com/trfdeer/cchipt/issues/new";
+
+// Maps the lowercase hex SHA-256 digest of a ROM to its metadata
+const CATALOG: &str = include_str!("../roms.json");
+// The same file as published in the repository, for entries added since this build
+pub const CATALOG_URL: &str = "https://raw.githubusercontent.com/trfdeer/cchipt/main/roms.json";
+
+type Catalog = HashMap<String, RomMetadata>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RomMetadata {
+    pub name: String,
+    pub author: String,
+    pub year: u16,
+    pub quirks_preset: QuirksPreset,
+    pub description: String,
+    pub difficulty: u8,
+}
+
+// Text some ROMs carry at the start, skipped over by a jump:
+//   0x200: JP past the text
+//   0x202: "Title by Author", more lines of description, separated by NUL or newlines
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+}
+
+impl HeaderMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.author.is_none() && self.description.is_none()
+    }
+}
+
+pub fn parse_metadata(rom: &[u8]) -> HeaderMetadata {
+    let header = match rom {
+        [high, low, rest @ ..] if high & 0xF0 == 0x10 => {
+            let target = u16::from_be_bytes([high & 0x0F, *low]) as usize;
+            target.checked_sub(0x202).and_then(|len| rest.get(..len))
+        }
+        _ => None,
+    };
+
+    // Anything that is not printable ASCII ends a line
+    let mut lines = header
+        .unwrap_or_default()
+        .split(|byte| !(0x20..=0x7E).contains(byte))
+        .map(|line| String::from_utf8_lossy(line).trim().to_string())
+        .filter(|line| line.len() > 1);
+
+    let mut metadata = HeaderMetadata::default();
+    if let Some(title) = lines.next() {
+        match title.split_once(" by ") {
+            Some((title, author)) => {
+                metadata.title = Some(title.trim().to_string());
+                metadata.author = Some(author.trim().to_string());
+            }
+            None => metadata.title = Some(title),
+        }
+    }
+    let mut description = Vec::new();
+    for line in lines {
+        match line.strip_prefix("by ") {
+            Some(author) if metadata.author.is_none() => {
+                metadata.author = Some(author.trim().to_string())
+            }
+            _ => description.push(line),
+        }
+    }
+    if !description.is_empty() {
+        metadata.description = Some(description.join(" "));
+    }
+    metadata
+}
+
+// ~/.cache/cchipt/roms.json on Linux
+pub fn cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("cchipt").join("roms.json"))
+}
+
+// Parsed on first use. Entries from a downloaded catalog take precedence over the bundled ones
+fn catalog() -> Result<&'static Catalog> {
+    static CATALOG_CELL: OnceLock<Result<Catalog, String>> = OnceLock::new();
+    let catalog = CATALOG_CELL.get_or_init(|| {
+        let mut catalog: Catalog = serde_json::from_str(CATALOG).map_err(|e| e.to_string())?;
+        let cached = cache_path().and_then(|path| fs::read_to_string(path).ok());
+        match cached.map(|json| serde_json::from_str::<Catalog>(&json)) {
+            Some(Ok(cached)) => catalog.extend(cached),
+            Some(Err(e)) => eprintln!("Ignoring the downloaded ROM catalog: {}", e),
+            None => {}
+        }
+        Ok(catalog)
+    });
+    catalog
+        .as_ref()
+        .map_err(|e| eyre!("ROM catalog is invalid: {}", e))
+}
+
+// Fetches the catalog into the cache the first time cchipt runs. The catalog has already been
+// read by then, so the new entries are used from the next launch
+pub fn download_if_missing() -> Result<()> {
+    let path = cache_path().ok_or_else(|| eyre!("no cache directory"))?;
+    if path.exists() {
+        return Ok(());
+    }
+    let json = reqwest::blocking::get(CATALOG_URL)?
+        .error_for_status()?
+        .text()?;
+    serde_json::from_str::<Catalog>(&json)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, json)?;
+    Ok(())
+}
+
+pub struct RomInfo {
+    pub sha256: String,
+    pub metadata: Option<RomMetadata>,
+    pub header: HeaderMetadata, // Only used when the ROM is not in the catalog
+}
+
+impl RomInfo {
+    pub fn new(rom: &[u8]) -> Result<Self> {
+        let sha256 = format!("{:x}", Sha256::digest(rom));
+        let metadata = catalog()?.get(&sha256).cloned();
+
+        Ok(Self {
+            sha256,
+            metadata,
+            header: parse_metadata(rom),
+        })
+    }
+
+    pub fn submit_url(&self) -> String {
+        format!(
+            "{}?template=rom_metadata.md&title=ROM+metadata+for+{}",
+            SUBMIT_METADATA_URL, self.sha256
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_catalog_parses() {
+        let catalog: Catalog = serde_json::from_str(CATALOG).unwrap();
+        assert!(catalog.keys().all(|sha256| {
+            sha256.len() == 64 && sha256.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+        }));
+    }
+
+    #[test]
+    fn known_rom_resolves_to_its_metadata() {
+        // Maze by David Winter
+        #[rustfmt::skip]
+        let rom = [
+            0xA2, 0x1E, 0xC2, 0x01, 0x32, 0x01, 0xA2, 0x1A, 0xD0, 0x14, 0x70, 0x04,
+            0x30, 0x40, 0x12, 0x00, 0x60, 0x00, 0x71, 0x04, 0x31, 0x20, 0x12, 0x00,
+            0x12, 0x18, 0x80, 0x40, 0x20, 0x10, 0x20, 0x40, 0x80, 0x10,
+        ];
+        let info = RomInfo::new(&rom).unwrap();
+        assert_eq!(
+            info.sha256,
+            "86437986e84b5c944f8883547b4380cbdaacb08503bf1cb65f7167782f786060"
+        );
+        let metadata = info.metadata.unwrap();
+        assert_eq!(metadata.name, "Maze");
+        assert_eq!(metadata.author, "David Winter");
+        assert_eq!(metadata.quirks_preset, QuirksPreset::CosmacVip);
+    }
+
+    #[test]
+    fn unknown_rom_falls_back_to_the_header() {
+        let mut rom = vec![0x12, 0x12];
+        rom.extend_from_slice(b"Maze by Someone\0");
+        let info = RomInfo::new(&rom).unwrap();
+        assert!(info.metadata.is_none());
+        assert_eq!(info.header.title.as_deref(), Some("Maze"));
+        assert_eq!(info.header.author.as_deref(), Some("Someone"));
+    }
+}
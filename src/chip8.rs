@@ -1,18 +1,288 @@
-use crate::emu::{CHARACTER_SPRITES, SCREEN_HEIGHT, SCREEN_WIDTH};
+pub mod assembler;
+mod compatibility;
+mod diff;
+mod framebuffer;
+mod keyboard;
+pub mod octo;
+mod snapshot;
+
+pub use compatibility::{Compatibility, CompatibilityMatrix};
+pub use diff::{diff_roms, DiffKind, RomDiffEntry};
+pub use framebuffer::{Framebuffer, ScrollUnit};
+pub use keyboard::MockKeyboard;
+pub use snapshot::Chip8Snapshot;
+
+use std::{collections::HashMap, fmt, ops::Range, path::Path};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_ROM_SIZE: usize = 4096 - 0x200;
+pub const FONT_ADDRESS: usize = 0x00;
+// The SUPER-CHIP 8x10 digits follow the small font
+pub const LARGE_FONT_ADDRESS: usize = 0x50;
+
+pub const SCREEN_WIDTH: u32 = 64;
+pub const SCREEN_HEIGHT: u32 = 32;
+
+pub const CHARACTER_SPRITES: [u8; 0x50] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x08, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x08, 0x80, // F
+];
+
+// SUPER-CHIP 8x10 digits, used by Fx30
+pub const LARGE_CHARACTER_SPRITES: [u8; 100] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum QuirksPreset {
+    CosmacVip,
+    Chip48,
+    SuperChip,
+}
+
+impl QuirksPreset {
+    pub const ALL: [QuirksPreset; 3] = [
+        QuirksPreset::CosmacVip,
+        QuirksPreset::Chip48,
+        QuirksPreset::SuperChip,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            QuirksPreset::CosmacVip => "COSMAC VIP",
+            QuirksPreset::Chip48 => "CHIP-48",
+            QuirksPreset::SuperChip => "SUPER-CHIP 1.1",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Mode {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip, // Also includes the SUPER-CHIP instructions
+}
+
+impl Mode {
+    pub const ALL: [Mode; 3] = [Mode::Chip8, Mode::SuperChip, Mode::XoChip];
+
+    // SUPER-CHIP 1.1 always scrolls in high resolution pixels, XO-CHIP in the current resolution
+    pub fn scroll_unit(&self) -> ScrollUnit {
+        match self {
+            Mode::SuperChip => ScrollUnit::HighResPixel,
+            Mode::Chip8 | Mode::XoChip => ScrollUnit::Pixel,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Chip8 => "CHIP-8",
+            Mode::SuperChip => "SUPER-CHIP 1.1",
+            Mode::XoChip => "XO-CHIP",
+        }
+    }
+}
+
+// Behaviours that differ between interpreters. With everything off, cchipt behaves as it always has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quirks {
+    pub vf_reset: bool,         // 8xy1/2/3 reset VF to 0
+    pub memory_increment: bool, // Fx55/Fx65 leave I pointing past the last register
+    pub shift_uses_vy: bool,    // 8xy6/8xyE shift Vy and store the result in Vx
+    pub jump_uses_vx: bool,     // Bnnn is decoded as Bxnn and jumps to xnn + Vx
+    pub clip_sprites: bool,     // Sprites are clipped at the screen edges instead of wrapping
+    pub display_wait: bool,     // DRW waits for the next frame
+    pub vf_keeps_result: bool,  // 8Fy4/5/7 store the result in VF instead of the flag
+}
+
+impl Quirks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl From<QuirksPreset> for Quirks {
+    fn from(preset: QuirksPreset) -> Self {
+        match preset {
+            QuirksPreset::CosmacVip => Self {
+                vf_reset: true,
+                memory_increment: true,
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+                clip_sprites: true,
+                display_wait: true,
+                vf_keeps_result: false,
+            },
+            // CHIP-48 increments I by x rather than x + 1, which is closer to leaving it alone
+            QuirksPreset::Chip48 | QuirksPreset::SuperChip => Self {
+                vf_reset: false,
+                memory_increment: false,
+                shift_uses_vy: false,
+                jump_uses_vx: true,
+                clip_sprites: true,
+                display_wait: false,
+                vf_keeps_result: false,
+            },
+        }
+    }
+}
+
+// Memory touched by an instruction, as addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryAccess {
+    Read(Range<u16>),
+    Write(Range<u16>),
+}
+
+#[derive(Debug)]
+pub enum Chip8Error {
+    RomTooLarge(usize),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::RomTooLarge(size) => write!(
+                f,
+                "ROM is {} bytes, but at most {} bytes fit in memory",
+                size, MAX_ROM_SIZE
+            ),
+            Chip8Error::Io(e) => write!(f, "failed to read ROM: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+// Conditions a program can run into that stop the CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    StackUnderflow(u16),         // Address of the RET
+    StackOverflow(u16),          // Address of the CALL
+    UnknownOpcode(u16, u16),     // Address, opcode
+    MemoryOutOfBounds(u16, u16), // Address, first byte the instruction reads or writes
+    Exited(u16),                 // Address of the EXIT
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::StackUnderflow(address) => {
+                write!(f, "RET at {:03x} with an empty stack", address)
+            }
+            CpuError::StackOverflow(address) => {
+                write!(f, "CALL at {:03x} with a full stack", address)
+            }
+            CpuError::UnknownOpcode(address, opcode) => {
+                write!(f, "unknown opcode {:04x} at {:03x}", opcode, address)
+            }
+            CpuError::MemoryOutOfBounds(address, start) => write!(
+                f,
+                "instruction at {:03x} accesses memory past the end, starting at {:04x}",
+                address, start
+            ),
+            CpuError::Exited(address) => write!(f, "program exited at {:03x}", address),
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+// Problems `Chip8::validate_rom` can spot without running the ROM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RomError {
+    UnknownOpcode(u16, u16), // Address, opcode
+    JumpOutOfBounds(u16),    // Address of the jump or call
+    RecursiveCall(u16),      // Address of a call to itself, which overflows the stack
+    TooLarge(usize),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::UnknownOpcode(address, opcode) => {
+                write!(f, "unknown opcode {:04x} at {:03x}", opcode, address)
+            }
+            RomError::JumpOutOfBounds(address) => {
+                write!(f, "jump at {:03x} leaves the program area", address)
+            }
+            RomError::RecursiveCall(address) => {
+                write!(f, "call at {:03x} calls itself", address)
+            }
+            RomError::TooLarge(size) => write!(
+                f,
+                "ROM is {} bytes, but at most {} bytes fit in memory",
+                size, MAX_ROM_SIZE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+impl From<std::io::Error> for Chip8Error {
+    fn from(e: std::io::Error) -> Self {
+        Chip8Error::Io(e)
+    }
+}
 
 #[allow(non_snake_case)]
+#[derive(Clone)]
 pub struct Chip8 {
-    pub V: [u8; 16],            // Vx registers; 0 through F. VF is used as flag
-    pub I: u16,                 // Index Register
-    pub delay_timer: u8,        // Delay Timer
-    pub sound_timer: u8,        // Sound Timer. Beeps when it reaches zero
-    pub stack: [u16; 16],       // Stack for storing return addresses, when calling subroutines
-    pub sp: u16,                // Stack Pointer
-    pub pc: u16,                // Program Counter
-    pub memory: [u8; 4096],     // 4KB RAM
-    pub key_states: [bool; 16], // 16-key Keyboard
-    pub gfx: [bool; 64 * 32],   // 64*32 Monochrome Display
-    pub make_beep: bool,        // Flag to signal if a beep is needed
+    pub V: [u8; 16],                // Vx registers; 0 through F. VF is used as flag
+    pub I: u16,                     // Index Register
+    pub delay_timer: u8,            // Delay Timer
+    pub sound_timer: u8,            // Sound Timer. Beeps when it reaches zero
+    pub stack: [u16; 16],           // Stack for storing return addresses, when calling subroutines
+    pub sp: u16,                    // Stack Pointer
+    pub pc: u16,                    // Program Counter
+    pub memory: [u8; 4096],         // 4KB RAM
+    pub key_states: [bool; 16],     // 16-key Keyboard, true while a key is held
+    pub key_presses: [bool; 16],    // Keys that went down since the last frame, used by Fx0A
+    pub gfx: Framebuffer,           // 64*32 Monochrome Display, 128*64 in high resolution mode
+    pub gfx2: Framebuffer,          // Second XO-CHIP bitplane
+    pub plane_mask: u8,             // XO-CHIP bitplanes drawn to, bit 0 is gfx and bit 1 is gfx2
+    pub make_beep: bool,            // Flag to signal if a beep is needed
+    pub sound_active: bool,         // The buzzer sounds for as long as the sound timer runs
+    pub quirks: Quirks,             // Interpreter dialect
+    pub mode: Mode,                 // Instruction set
+    pub rpl_flags: [u8; 8],         // SUPER-CHIP user flags, kept outside of memory
+    pub audio_buffer: [u8; 16],     // XO-CHIP 1-bit audio pattern, 128 samples
+    pub audio_pitch: u8,            // XO-CHIP playback rate of the audio pattern
+    pub vblank: bool,               // Set every frame, DRW waits for it with the display wait quirk
+    pub profile: HashMap<u16, u64>, // Instructions executed, keyed by `opcode_category`
+    pub rng: SmallRng,              // Source of RND, cloned with the rest of the state
+    pub gfx_dirty: bool,            // Display changed since it was last drawn
+    pub font: [u8; 0x50],           // Small font, written to memory on every reset
+    pub halted: Option<CpuError>,   // Set when the program can not go on, ticks do nothing
 }
 
 impl Chip8 {
@@ -27,22 +297,155 @@ impl Chip8 {
             pc: 0x200, // Execution starts at 0x200
             memory: [0u8; 4096],
             key_states: [false; 16],
-            gfx: [false; 64 * 32],
+            key_presses: [false; 16],
+            gfx: Framebuffer::default(),
+            gfx2: Framebuffer::default(),
+            plane_mask: 1,
             make_beep: false,
+            sound_active: false,
+            quirks: Quirks::new(),
+            mode: Mode::default(),
+            rpl_flags: [0u8; 8],
+            audio_buffer: [0u8; 16],
+            audio_pitch: 64, // 4000 Hz
+            vblank: false,
+            profile: HashMap::new(),
+            rng: SmallRng::from_entropy(),
+            gfx_dirty: true,
+            font: CHARACTER_SPRITES,
+            halted: None,
         };
 
         // Load charaters into memory for display
-        new_cpu.memory[0x00..0x50].copy_from_slice(&CHARACTER_SPRITES);
+        new_cpu.memory[FONT_ADDRESS..(FONT_ADDRESS + CHARACTER_SPRITES.len())]
+            .copy_from_slice(&CHARACTER_SPRITES);
+        new_cpu.memory[LARGE_FONT_ADDRESS..(LARGE_FONT_ADDRESS + LARGE_CHARACTER_SPRITES.len())]
+            .copy_from_slice(&LARGE_CHARACTER_SPRITES);
 
         new_cpu
     }
 
-    pub fn tick(&mut self) {
-        self.execute_opcode();
-        self.update_timers();
+    // Replaces the small font, now and after a reset
+    pub fn load_font(&mut self, data: &[u8; 0x50]) {
+        self.font = *data;
+        self.memory[FONT_ADDRESS..(FONT_ADDRESS + data.len())].copy_from_slice(data);
+    }
+
+    // Where the 4x5 sprite for a hex digit starts, None if `digit` is over 0xF
+    pub fn sprite_address(digit: u8) -> Option<u16> {
+        (digit <= 0xF).then(|| (FONT_ADDRESS + digit as usize * 5) as u16)
+    }
+
+    // Where the 8x10 sprite for a decimal digit starts, None if `digit` is over 9
+    pub fn large_sprite_address(digit: u8) -> Option<u16> {
+        (digit <= 9).then(|| (LARGE_FONT_ADDRESS + digit as usize * 10) as u16)
     }
 
-    fn update_timers(&mut self) {
+    // Font sprites as currently in memory, which a ROM is free to overwrite
+    pub fn get_sprite_at(&self, digit: u8) -> Option<&[u8; 5]> {
+        let start = Self::sprite_address(digit)? as usize;
+        self.memory[start..(start + 5)].try_into().ok()
+    }
+
+    pub fn get_large_sprite_at(&self, digit: u8) -> Option<&[u8; 10]> {
+        let start = Self::large_sprite_address(digit)? as usize;
+        self.memory[start..(start + 10)].try_into().ok()
+    }
+
+    // RND produces the same numbers on every run, for tests and replays
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        let mut cpu = Self::new();
+        cpu.rng = SmallRng::seed_from_u64(seed);
+        cpu
+    }
+
+    // Replaces the program, leaving the rest of the state alone
+    pub fn load_bytes(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        if data.len() > MAX_ROM_SIZE {
+            return Err(Chip8Error::RomTooLarge(data.len()));
+        }
+
+        self.memory[0x200..].fill(0);
+        self.memory[0x200..(0x200 + data.len())].copy_from_slice(data);
+        Ok(())
+    }
+
+    // Best-effort sanity check, run before loading. Every even offset is read as an
+    // instruction, so sprite data can hide problems or, in the 0nnn and Ennn families, be
+    // reported as one. Unknown opcodes in the other families are left alone for that reason
+    pub fn validate_rom(data: &[u8]) -> Result<(), RomError> {
+        if data.len() > MAX_ROM_SIZE {
+            return Err(RomError::TooLarge(data.len()));
+        }
+
+        for (i, word) in data.chunks_exact(2).enumerate() {
+            let address = 0x200 + 2 * i as u16;
+            let opcode = u16::from_be_bytes([word[0], word[1]]);
+            let nnn = opcode & 0x0FFF;
+            match opcode & 0xF000 {
+                // Zeroed padding is not worth a warning
+                0x0000 | 0xE000 if opcode != 0x0000 => {
+                    let mnemonic = Self::decode_instruction(&opcode);
+                    if mnemonic.is_empty() || mnemonic.starts_with("UNK") {
+                        return Err(RomError::UnknownOpcode(address, opcode));
+                    }
+                }
+                0x1000 | 0x2000 if nnn < 0x200 => {
+                    return Err(RomError::JumpOutOfBounds(address));
+                }
+                0x2000 if nnn == address => return Err(RomError::RecursiveCall(address)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Address of the first run of `pattern` at or after `from`, going on from the start of
+    // memory when nothing turns up before the end
+    pub fn search_memory(memory: &[u8; 4096], pattern: &[u8], from: u16) -> Option<u16> {
+        if pattern.is_empty() || pattern.len() > memory.len() {
+            return None;
+        }
+        let last_start = memory.len() - pattern.len();
+        let from = (from as usize).min(last_start + 1);
+        (from..=last_start)
+            .chain(0..from)
+            .find(|&start| memory[start..].starts_with(pattern))
+            .map(|start| start as u16)
+    }
+
+    // Restarts the program. The ROM in memory, the interpreter settings, the font and the RPL
+    // flags, which are meant to survive between runs, are kept. So is the RNG, to keep a seeded
+    // session reproducible
+    pub fn reset(&mut self) {
+        let mut new_cpu = Self::new();
+        new_cpu.memory[0x200..].copy_from_slice(&self.memory[0x200..]);
+        new_cpu.quirks = self.quirks;
+        new_cpu.mode = self.mode;
+        new_cpu.rpl_flags = self.rpl_flags;
+        new_cpu.rng = self.rng.clone();
+        new_cpu.load_font(&self.font);
+        *self = new_cpu;
+    }
+
+    // Only the tick that halts returns the error, ticks after that do nothing until it is cleared.
+    // Timers are left to `tick_timers`, as they count at 60 Hz whatever the clock rate
+    pub fn tick(&mut self) -> Result<(), CpuError> {
+        if self.halted.is_some() {
+            return Ok(());
+        }
+        if let Err(error) = self.execute_opcode() {
+            self.halted = Some(error);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    pub fn tick_timers(&mut self) {
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -50,23 +453,115 @@ impl Chip8 {
         if self.sound_timer > 0 {
             if self.sound_timer == 1 {
                 self.make_beep = true;
+                self.sound_active = false;
             }
             self.sound_timer -= 1;
         }
     }
 
+    // Bytes past the end of memory read as 0, executing there halts instead
     pub fn get_opcode(&self) -> u16 {
-        u16::from_be_bytes([
-            self.memory[self.pc as usize],
-            self.memory[(self.pc + 1) as usize],
-        ])
+        let byte = |address: usize| self.memory.get(address).copied().unwrap_or(0);
+        u16::from_be_bytes([byte(self.pc as usize), byte(self.pc as usize + 1)])
+    }
+
+    // Reads memory as a program from `start`, as (address, opcode, mnemonic). Ends before an
+    // opcode that would run past the end of memory
+    pub fn iter_instructions(&self, start: u16) -> impl Iterator<Item = (u16, u16, String)> + '_ {
+        (start..0xFFF).step_by(2).map(|address| {
+            let opcode = u16::from_be_bytes([
+                self.memory[address as usize],
+                self.memory[address as usize + 1],
+            ]);
+            let mnemonic = match Self::decode_instruction(&opcode) {
+                mnemonic if mnemonic.is_empty() || mnemonic.starts_with("UNK") => {
+                    String::from("???")
+                }
+                mnemonic => mnemonic,
+            };
+            (address, opcode, mnemonic)
+        })
+    }
+
+    // The range is clamped to memory, so a range past the end or backwards is cut short or empty
+    pub fn memory_checksum(&self, start: u16, end: u16) -> u32 {
+        let end = (end as usize).min(self.memory.len());
+        let start = (start as usize).min(end);
+        crc32fast::hash(&self.memory[start..end])
+    }
+
+    // Back to a blank 64*32 display with only the first bitplane selected
+    pub fn reset_display(&mut self) {
+        self.gfx = Framebuffer::default();
+        self.gfx2 = Framebuffer::default();
+        self.plane_mask = 1;
+        self.gfx_dirty = true;
+    }
+
+    fn selected_planes(&mut self) -> impl Iterator<Item = &mut Framebuffer> {
+        let mask = self.plane_mask;
+        [&mut self.gfx, &mut self.gfx2]
+            .into_iter()
+            .enumerate()
+            .filter(move |(i, _)| mask & (1 << i) != 0)
+            .map(|(_, plane)| plane)
+    }
+
+    // The memory the instruction at pc will read or write when it runs, going by the current
+    // registers. Fetching the opcode itself does not count
+    pub fn memory_access(&self) -> Option<MemoryAccess> {
+        let opcode = self.get_opcode();
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        let range = |len: u16| self.I..self.I.saturating_add(len);
+
+        match opcode & 0xF000 {
+            0x5000 if self.mode == Mode::XoChip => match opcode & 0x000F {
+                0x0002 => Some(MemoryAccess::Write(range(x.abs_diff(y) + 1))),
+                0x0003 => Some(MemoryAccess::Read(range(x.abs_diff(y) + 1))),
+                _ => None,
+            },
+            0xD000 => {
+                let n = opcode & 0x000F;
+                let sprite_len = if n == 0 && self.mode != Mode::Chip8 {
+                    32
+                } else {
+                    n
+                };
+                let planes = (self.plane_mask & 0b11).count_ones() as u16;
+                Some(MemoryAccess::Read(range(sprite_len * planes)))
+            }
+            0xF000 => match opcode & 0x00FF {
+                0x0002 if self.mode == Mode::XoChip && x == 0 => {
+                    Some(MemoryAccess::Read(range(16)))
+                }
+                0x0033 => Some(MemoryAccess::Write(range(3))),
+                0x0055 => Some(MemoryAccess::Write(range(x + 1))),
+                0x0065 => Some(MemoryAccess::Read(range(x + 1))),
+                _ => None,
+            },
+            _ => None,
+        }
     }
 
     pub fn decode_instruction(opcode: &u16) -> String {
         match opcode & 0xF000 {
-            0x0000 => match opcode & 0x000F {
-                0x0000 => String::from("CLS"),
-                0x000E => String::from("RET"),
+            0x0000 => match opcode {
+                0x00E0 => String::from("CLS"),
+                0x00EE => String::from("RET"),
+                0x00C0..=0x00CF => {
+                    let n = (opcode & 0x000F) as u8;
+                    format!("{:4} {n:x}", "SCD")
+                }
+                0x00D0..=0x00DF => {
+                    let n = (opcode & 0x000F) as u8;
+                    format!("{:4} {n:x}", "SCU")
+                }
+                0x00FB => String::from("SCR"),
+                0x00FC => String::from("SCL"),
+                0x00FD => String::from("EXIT"),
+                0x00FE => String::from("LOW"),
+                0x00FF => String::from("HIGH"),
                 _ => String::default(),
             },
             0x1000 => {
@@ -90,7 +585,12 @@ impl Chip8 {
             0x5000 => {
                 let x = ((opcode & 0x0F00) >> 8) as u8;
                 let y = ((opcode & 0x00F0) >> 4) as u8;
-                format!("{:4} V{x:X}, V{y:X}", "SE")
+                match opcode & 0x000F {
+                    0x0000 => format!("{:4} V{x:X}, V{y:X}", "SE"),
+                    0x0002 => format!("{:4} [I], V{x:X}-V{y:X}", "LD"),
+                    0x0003 => format!("{:4} V{x:X}-V{y:X}, [I]", "LD"),
+                    _ => format!("UNK {:04x}", opcode),
+                }
             }
             0x6000 => {
                 let x = ((opcode & 0x0F00) >> 8) as u8;
@@ -116,7 +616,7 @@ impl Chip8 {
                     0x0006 => format!("{:4} V{x:X}, V{y:X}", "SHR"),
                     0x0007 => format!("{:4} V{x:X}, V{y:X}", "SUBN"),
                     0x000E => format!("{:4} V{x:X}, V{y:X}", "SHL"),
-                    _ => unreachable!(),
+                    _ => format!("UNK {:04x}", opcode),
                 }
             }
             0x9000 => {
@@ -148,48 +648,196 @@ impl Chip8 {
                 match opcode & 0x000F {
                     0x000E => format!("{:4} V{x:X}", "SKP"),
                     0x0001 => format!("{:4} V{x:X}", "SKNP"),
-                    _ => unreachable!(),
+                    _ => format!("UNK {:04x}", opcode),
                 }
             }
             0xF000 => {
                 let x = ((opcode & 0x0F00) >> 8) as u8;
                 match opcode & 0x00FF {
+                    // The address follows in the next word
+                    0x0000 if x == 0 => format!("{:4} I, LONG", "LD"),
+                    0x0001 => format!("{:4} {x:x}", "PLN"),
+                    0x0002 if x == 0 => format!("{:4} AUDIO, [I]", "LD"),
                     0x0007 => format!("{:4} V{x:X}, DT", "LD"),
                     0x000A => format!("{:4} V{x:X}, K", "LD"),
                     0x0015 => format!("{:4} DT, V{x:X}", "LD"),
                     0x0018 => format!("{:4} ST, V{x:X}", "LD"),
                     0x001E => format!("{:4} I, V{x:X}", "ADD"),
                     0x0029 => format!("{:4} F, V{x:X}", "LD"),
+                    0x0030 => format!("{:4} HF, V{x:X}", "LD"),
                     0x0033 => format!("{:4} B, V{x:X}", "LD"),
+                    0x003A => format!("{:4} PITCH, V{x:X}", "LD"),
                     0x0055 => format!("{:4} [I], V{x:X}", "LD"),
                     0x0065 => format!("{:4} V{x:X}, [I]", "LD"),
-                    _ => unreachable!(),
+                    0x0075 => format!("{:4} R, V{x:X}", "LD"),
+                    0x0085 => format!("{:4} V{x:X}, R", "LD"),
+                    _ => format!("UNK {:04x}", opcode),
                 }
             }
-            _ => unreachable!(),
+            _ => format!("UNK {:04x}", opcode),
         }
     }
 
-    fn execute_opcode(&mut self) {
+    // Bits of an opcode that are not operands
+    fn category_mask(opcode: u16) -> u16 {
+        match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00C0..=0x00DF => 0xFFF0,
+                0x00E0 | 0x00EE | 0x00FB..=0x00FF => 0xFFFF,
+                _ => 0xF000,
+            },
+            0x5000 | 0x8000 | 0x9000 => 0xF00F,
+            0xE000 | 0xF000 => 0xF0FF,
+            _ => 0xF000,
+        }
+    }
+
+    // The opcode with its operands masked out, e.g. 0x8124 becomes 0x8004
+    pub fn opcode_category(opcode: u16) -> u16 {
+        opcode & Self::category_mask(opcode)
+    }
+
+    // Opcode pattern for a category, e.g. "8xy4"
+    pub fn category_name(category: u16) -> String {
+        let operands = match category & 0xF000 {
+            0x0000 | 0x1000 | 0x2000 | 0xA000 | 0xB000 => "?nnn",
+            0x3000 | 0x4000 | 0x6000 | 0x7000 | 0xC000 => "?xkk",
+            0xE000 | 0xF000 => "?x??",
+            _ => "?xyn",
+        };
+        let mask = Self::category_mask(category);
+        let digits = format!("{:04X}", category);
+        digits
+            .chars()
+            .zip(operands.chars())
+            .enumerate()
+            .map(|(i, (digit, operand))| match (mask >> (12 - 4 * i)) & 0xF {
+                0 => operand,
+                _ => digit,
+            })
+            .collect()
+    }
+
+    // Real interpreters write VF last, so with x = F the flag replaces the result
+    fn set_arithmetic_result(&mut self, x: u8, result: u8, flag: u8) {
+        if self.quirks.vf_keeps_result {
+            self.V[0xF] = flag;
+            self.V[x as usize] = result;
+        } else {
+            self.V[x as usize] = result;
+            self.V[0xF] = flag;
+        }
+    }
+
+    // Memory an instruction accesses, `len` bytes from `start`, if it all fits
+    fn memory_range(&self, start: usize, len: usize) -> Result<Range<usize>, CpuError> {
+        if start + len > self.memory.len() {
+            return Err(CpuError::MemoryOutOfBounds(self.pc, start as u16));
+        }
+        Ok(start..(start + len))
+    }
+
+    // Moves pc past the instruction after the current one, which in XO-CHIP can be the 4-byte
+    // F000 nnnn
+    fn skip_next(&mut self) {
+        let next = self.pc.wrapping_add(2);
+        let long = self.mode == Mode::XoChip
+            && self.memory.get(next as usize..next as usize + 2) == Some(&[0xF0, 0x00][..]);
+        self.pc += if long { 4 } else { 2 };
+    }
+
+    // Lets the program carry on from where it stopped, e.g. after fixing memory or registers
+    pub fn clear_halt(&mut self) {
+        self.halted = None;
+    }
+
+    fn execute_opcode(&mut self) -> Result<(), CpuError> {
+        self.memory_range(self.pc as usize, 2)?;
         let opcode = self.get_opcode();
+        *self
+            .profile
+            .entry(Self::opcode_category(opcode))
+            .or_insert(0) += 1;
         match opcode & 0xF000 {
-            0x0000 => match opcode & 0x000F {
+            0x0000 => match opcode {
                 // 00E0 - CLS
                 // Clear the display.
-                0x0000 => {
-                    self.gfx = [false; 64 * 32];
+                0x00E0 => {
+                    self.selected_planes().for_each(Framebuffer::clear);
+                    self.gfx_dirty = true;
                     self.pc += 2;
                 }
-                // 1nnn - JP addr
-                // Jump to location nnn.
-                0x000E => {
+                // 00EE - RET
+                // Return from a subroutine.
+                0x00EE => {
+                    if self.sp == 0 {
+                        return Err(CpuError::StackUnderflow(self.pc));
+                    }
                     self.sp -= 1;
                     self.pc = self.stack[self.sp as usize];
                     self.pc += 2;
                 }
+                // 00Cn - SCD nibble
+                // Scroll the display down by n rows.
+                0x00C0..=0x00CF if self.mode != Mode::Chip8 => {
+                    let n = (opcode & 0x000F) as usize;
+                    let unit = self.mode.scroll_unit();
+                    self.selected_planes()
+                        .for_each(|plane| plane.scroll_down(n, unit));
+                    self.gfx_dirty = true;
+                    self.pc += 2;
+                }
+                // 00Dn - SCU nibble
+                // Scroll the display up by n rows.
+                0x00D0..=0x00DF if self.mode == Mode::XoChip => {
+                    let n = (opcode & 0x000F) as usize;
+                    let unit = self.mode.scroll_unit();
+                    self.selected_planes()
+                        .for_each(|plane| plane.scroll_up(n, unit));
+                    self.gfx_dirty = true;
+                    self.pc += 2;
+                }
+                // 00FB - SCR
+                // Scroll the display right by 4 pixels.
+                0x00FB if self.mode != Mode::Chip8 => {
+                    let unit = self.mode.scroll_unit();
+                    self.selected_planes()
+                        .for_each(|plane| plane.scroll_right(4, unit));
+                    self.gfx_dirty = true;
+                    self.pc += 2;
+                }
+                // 00FC - SCL
+                // Scroll the display left by 4 pixels.
+                0x00FC if self.mode != Mode::Chip8 => {
+                    let unit = self.mode.scroll_unit();
+                    self.selected_planes()
+                        .for_each(|plane| plane.scroll_left(4, unit));
+                    self.gfx_dirty = true;
+                    self.pc += 2;
+                }
+                // 00FD - EXIT
+                // Exit the interpreter. The CPU halts here until it is reset.
+                0x00FD if self.mode != Mode::Chip8 => return Err(CpuError::Exited(self.pc)),
+                // 00FE - LOW
+                // Switch to 64x32 low resolution mode.
+                0x00FE if self.mode != Mode::Chip8 => {
+                    self.gfx.resize(64, 32);
+                    self.gfx2.resize(64, 32);
+                    self.gfx_dirty = true;
+                    self.pc += 2;
+                }
+                // 00FF - HIGH
+                // Switch to 128x64 high resolution mode.
+                0x00FF if self.mode != Mode::Chip8 => {
+                    self.gfx.resize(128, 64);
+                    self.gfx2.resize(128, 64);
+                    self.gfx_dirty = true;
+                    self.pc += 2;
+                }
                 // 0nnn - SYS addr (Not Implemented)
-                // Jump to a machine code routine at nnn.
-                _ => {}
+                // Jump to a machine code routine at nnn. Also reached by the SUPER-CHIP and
+                // XO-CHIP opcodes above when the mode does not have them
+                _ => return Err(CpuError::UnknownOpcode(self.pc, opcode)),
             },
             // 1nnn - JP addr
             // Jump to location nnn.
@@ -201,6 +849,9 @@ impl Chip8 {
             // Call subroutine at nnn.
             0x2000 => {
                 let nnn = opcode & 0x0FFF;
+                if self.sp as usize >= self.stack.len() {
+                    return Err(CpuError::StackOverflow(self.pc));
+                }
                 self.stack[self.sp as usize] = self.pc;
                 self.sp += 1;
                 self.pc = nnn;
@@ -211,7 +862,7 @@ impl Chip8 {
                 let x = ((opcode & 0x0F00) >> 8) as u8;
                 let kk = (opcode & 0x00FF) as u8;
                 if self.V[x as usize] == kk {
-                    self.pc += 2;
+                    self.skip_next();
                 }
 
                 self.pc += 2;
@@ -222,20 +873,50 @@ impl Chip8 {
                 let x = ((opcode & 0x0F00) >> 8) as u8;
                 let kk = (opcode & 0x00FF) as u8;
                 if self.V[x as usize] != kk {
-                    self.pc += 2;
+                    self.skip_next();
                 }
 
                 self.pc += 2;
             }
-            // 5xy0 - SE Vx, Vy
-            // Skip next instruction if Vx = Vy.
             0x5000 => {
-                let x = ((opcode & 0x0F00) >> 8) as u8;
-                let y = ((opcode & 0x00F0) >> 4) as u8;
-                if self.V[x as usize] == self.V[y as usize] {
-                    self.pc += 2;
+                let x = ((opcode & 0x0F00) >> 8) as usize;
+                let y = ((opcode & 0x00F0) >> 4) as usize;
+                // Registers x through y, which may count down
+                let registers = if x <= y {
+                    (x..=y).collect::<Vec<_>>()
+                } else {
+                    (y..=x).rev().collect()
+                };
+
+                match opcode & 0x000F {
+                    // 5xy2 - LD [I], Vx-Vy
+                    // Store registers Vx through Vy in memory starting at location I.
+                    0x0002 if self.mode == Mode::XoChip => {
+                        let range = self.memory_range(self.I as usize, registers.len())?;
+                        for (address, register) in range.zip(registers) {
+                            self.memory[address] = self.V[register];
+                        }
+                        self.pc += 2;
+                    }
+                    // 5xy3 - LD Vx-Vy, [I]
+                    // Read registers Vx through Vy from memory starting at location I.
+                    0x0003 if self.mode == Mode::XoChip => {
+                        let range = self.memory_range(self.I as usize, registers.len())?;
+                        for (address, register) in range.zip(registers) {
+                            self.V[register] = self.memory[address];
+                        }
+                        self.pc += 2;
+                    }
+                    // 5xy0 - SE Vx, Vy
+                    // Skip next instruction if Vx = Vy.
+                    0x0000 => {
+                        if self.V[x] == self.V[y] {
+                            self.skip_next();
+                        }
+                        self.pc += 2;
+                    }
+                    _ => return Err(CpuError::UnknownOpcode(self.pc, opcode)),
                 }
-                self.pc += 2;
             }
             // 6xkk - LD Vx, byte
             // Set Vx = kk.
@@ -268,18 +949,27 @@ impl Chip8 {
                     // Set Vx = Vx OR Vy.
                     0x0001 => {
                         self.V[x as usize] |= self.V[y as usize];
+                        if self.quirks.vf_reset {
+                            self.V[0xF_usize] = 0;
+                        }
                         self.pc += 2;
                     }
                     // 8xy2 - AND Vx, Vy
                     // Set Vx = Vx AND Vy.
                     0x0002 => {
                         self.V[x as usize] &= self.V[y as usize];
+                        if self.quirks.vf_reset {
+                            self.V[0xF_usize] = 0;
+                        }
                         self.pc += 2;
                     }
                     // 8xy3 - XOR Vx, Vy
                     // Set Vx = Vx XOR Vy.
                     0x0003 => {
                         self.V[x as usize] ^= self.V[y as usize];
+                        if self.quirks.vf_reset {
+                            self.V[0xF_usize] = 0;
+                        }
                         self.pc += 2;
                     }
                     // 8xy4 - ADD Vx, Vy
@@ -289,8 +979,7 @@ impl Chip8 {
                         let vy = self.V[y as usize];
                         let (sum, carry) = vx.overflowing_add(vy);
 
-                        self.V[x as usize] = sum;
-                        self.V[0xF_usize] = if carry { 1 } else { 0 };
+                        self.set_arithmetic_result(x, sum, carry as u8);
                         self.pc += 2;
                     }
                     // 8xy5 - SUB Vx, Vy
@@ -300,17 +989,19 @@ impl Chip8 {
                         let vy = self.V[y as usize];
                         let (diff, borrow) = vx.overflowing_sub(vy);
 
-                        self.V[x as usize] = diff;
-                        self.V[0xF_usize] = if borrow { 0 } else { 1 };
+                        self.set_arithmetic_result(x, diff, !borrow as u8);
                         self.pc += 2;
                     }
                     // 8xy6 - SHR Vx {, Vy}
                     // Set Vx = Vx SHR 1.
                     0x0006 => {
-                        let vx = self.V[x as usize];
-                        // let vy = self.V[y as usize];
+                        let vx = if self.quirks.shift_uses_vy {
+                            self.V[y as usize]
+                        } else {
+                            self.V[x as usize]
+                        };
 
-                        self.V[x as usize] >>= 1;
+                        self.V[x as usize] = vx >> 1;
                         self.V[0xF_usize] = vx & 1;
                         self.pc += 2;
                     }
@@ -321,21 +1012,23 @@ impl Chip8 {
                         let vy = self.V[y as usize];
                         let (diff, borrow) = vy.overflowing_sub(vx);
 
-                        self.V[x as usize] = diff;
-                        self.V[0xF_usize] = if borrow { 0 } else { 1 };
+                        self.set_arithmetic_result(x, diff, !borrow as u8);
                         self.pc += 2;
                     }
                     // 8xyE - SHL Vx {, Vy}
                     // Set Vx = Vx SHL 1.
                     0x000E => {
-                        let vx = self.V[x as usize];
-                        // let vy = self.V[y as usize];
+                        let vx = if self.quirks.shift_uses_vy {
+                            self.V[y as usize]
+                        } else {
+                            self.V[x as usize]
+                        };
 
-                        self.V[x as usize] <<= 1;
+                        self.V[x as usize] = vx << 1;
                         self.V[0xF_usize] = (vx >> 7) & 1;
                         self.pc += 2;
                     }
-                    _ => unreachable!(),
+                    _ => return Err(CpuError::UnknownOpcode(self.pc, opcode)),
                 }
             }
             // 9xy0 - SNE Vx, Vy
@@ -344,10 +1037,9 @@ impl Chip8 {
                 let x = ((opcode & 0x0F00) >> 8) as u8;
                 let y = ((opcode & 0x00F0) >> 4) as u8;
                 if self.V[x as usize] != self.V[y as usize] {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
+                    self.skip_next();
                 }
+                self.pc += 2;
             }
             // Annn - LD I, addr
             // Set I = nnn.
@@ -360,7 +1052,12 @@ impl Chip8 {
             // Jump to location nnn + V0.
             0xB000 => {
                 let nnn = opcode & 0x0FFF;
-                self.pc = self.V[0] as u16 + nnn;
+                let x = if self.quirks.jump_uses_vx {
+                    ((opcode & 0x0F00) >> 8) as usize
+                } else {
+                    0
+                };
+                self.pc = self.V[x] as u16 + nnn;
             }
             // Cxkk - RND Vx, byte
             // Set Vx = random byte AND kk.
@@ -368,37 +1065,52 @@ impl Chip8 {
                 let x = ((opcode & 0x0F00) >> 8) as u8;
                 let kk = (opcode & 0x00FF) as u8;
 
-                let rand_byte = rand::random::<u8>();
+                let rand_byte = self.rng.gen::<u8>();
                 self.V[x as usize] = rand_byte & kk;
                 self.pc += 2;
             }
             // Dxyn - DRW Vx, Vy, nibble
             // Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
             0xD000 => {
+                // pc is left alone until the next frame, so this opcode runs again
+                if self.quirks.display_wait && !self.vblank {
+                    return Ok(());
+                }
+                self.vblank = false;
+
+                let (width, height) = (self.gfx.width(), self.gfx.height());
+
                 let x = ((opcode & 0x0F00) >> 8) as usize;
-                let vx = self.V[x as usize] as usize;
+                let vx = self.V[x] as usize % width;
 
                 let y = ((opcode & 0x00F0) >> 4) as usize;
-                let vy = self.V[y as usize] as usize;
+                let vy = self.V[y] as usize % height;
 
+                // Dxy0 draws a 16x16 sprite in SUPER-CHIP mode, two bytes per row
                 let n = (opcode & 0x000F) as usize;
-                let bytes = &self.memory[(self.I as usize)..(self.I as usize + n)];
+                let (sprite_width, rows) = if n == 0 && self.mode != Mode::Chip8 {
+                    (16, 16)
+                } else {
+                    (8, n)
+                };
+                let sprite_len = rows * sprite_width / 8;
                 let mut collision = false;
 
-                for (row, _) in bytes.iter().enumerate().take(n) {
-                    let byte = bytes[row];
-                    for col in 0..8 {
-                        let index = ((row + vy) % SCREEN_HEIGHT as usize) * 64
-                            + ((col + vx) % SCREEN_WIDTH as usize);
-                        let cur_val = if self.gfx[index] { 1 } else { 0 };
-                        let new_val = cur_val ^ ((byte & (0x80 >> col)) >> (7 - col));
-                        if new_val == 0 && cur_val == 1 {
-                            collision = true;
-                        }
-                        self.gfx[index] = new_val == 1;
+                // With both XO-CHIP bitplanes selected, the second sprite follows the first
+                let planes = (self.plane_mask & 0b11).count_ones() as usize;
+                self.memory_range(self.I as usize, sprite_len * planes)?;
+                let mut start = self.I as usize;
+                for (i, plane) in [&mut self.gfx, &mut self.gfx2].into_iter().enumerate() {
+                    if self.plane_mask & (1 << i) == 0 {
+                        continue;
                     }
+                    let sprite = &self.memory[start..(start + sprite_len)];
+                    collision |=
+                        plane.draw_sprite(vx, vy, sprite, sprite_width, self.quirks.clip_sprites);
+                    start += sprite_len;
                 }
                 self.V[0xF_usize] = if collision { 1 } else { 0 };
+                self.gfx_dirty = true;
 
                 self.pc += 2;
             }
@@ -408,27 +1120,51 @@ impl Chip8 {
                     // Ex9E - SKP Vx
                     // Skip next instruction if key with the value of Vx is pressed.
                     0x000E => {
-                        let vx = self.V[x as usize];
+                        // Only the low nibble picks a key, as there are just 16
+                        let vx = self.V[x as usize] & 0xF;
                         if self.key_states[vx as usize] {
-                            self.pc += 2;
+                            self.skip_next();
                         }
                         self.pc += 2;
                     }
                     // ExA1 - SKNP Vx
                     // Skip next instruction if key with the value of Vx is not pressed.
                     0x0001 => {
-                        let vx = self.V[x as usize];
+                        let vx = self.V[x as usize] & 0xF;
                         if !self.key_states[vx as usize] {
-                            self.pc += 2;
+                            self.skip_next();
                         }
                         self.pc += 2;
                     }
-                    _ => unreachable!(),
+                    _ => return Err(CpuError::UnknownOpcode(self.pc, opcode)),
                 }
             }
             0xF000 => {
                 let x = ((opcode & 0x0F00) >> 8) as u8;
                 match opcode & 0x00FF {
+                    // F000 nnnn - LD I, LONG nnnn
+                    // Set I = nnnn, the 16-bit address in the next word.
+                    0x0000 if self.mode == Mode::XoChip && x == 0 => {
+                        let range = self.memory_range(self.pc as usize + 2, 2)?;
+                        self.I = u16::from_be_bytes([
+                            self.memory[range.start],
+                            self.memory[range.start + 1],
+                        ]);
+                        self.pc += 4;
+                    }
+                    // Fn01 - PLN n
+                    // Select the bitplanes drawn to.
+                    0x0001 if self.mode == Mode::XoChip => {
+                        self.plane_mask = x & 0x3;
+                        self.pc += 2;
+                    }
+                    // F002 - LD AUDIO, [I]
+                    // Load the 16-byte audio pattern starting at location I.
+                    0x0002 if self.mode == Mode::XoChip && x == 0 => {
+                        let range = self.memory_range(self.I as usize, 16)?;
+                        self.audio_buffer.copy_from_slice(&self.memory[range]);
+                        self.pc += 2;
+                    }
                     // Fx07 - LD Vx, DT
                     // Set Vx = delay timer value.
                     0x0007 => {
@@ -438,11 +1174,12 @@ impl Chip8 {
                     // Fx0A - LD Vx, K
                     // Wait for a key press, store the value of the key in Vx.
                     0x000A => {
-                        for (i, key) in self.key_states.iter().enumerate() {
-                            if *key {
-                                self.V[x as usize] = i as u8;
-                                self.pc += 2;
-                            }
+                        // pc is left alone until a key is pressed, so this opcode runs again next
+                        // tick. A key that was already held does not count
+                        if let Some(key) = self.key_presses.iter().position(|key| *key) {
+                            self.key_presses[key] = false;
+                            self.V[x as usize] = key as u8;
+                            self.pc += 2;
                         }
                     }
                     // Fx15 - LD DT, Vx
@@ -455,52 +1192,85 @@ impl Chip8 {
                     // Set sound timer = Vx.
                     0x0018 => {
                         self.sound_timer = self.V[x as usize];
+                        self.sound_active = self.sound_timer > 0;
                         self.pc += 2;
                     }
                     // Fx1E - ADD I, Vx
                     // Set I = I + Vx.
                     0x001E => {
-                        self.I += self.V[x as usize] as u16;
+                        // Wraps like the 16-bit register it is, memory accesses past the end halt
+                        self.I = self.I.wrapping_add(self.V[x as usize] as u16);
                         self.pc += 2;
                     }
                     // Fx29 - LD F, Vx
                     // Set I = location of sprite for digit Vx.
                     0x0029 => {
-                        let vx = self.V[x as usize];
-                        self.I = (vx * 0x5) as u16;
+                        self.I = Self::sprite_address(self.V[x as usize] & 0xF)
+                            .expect("masked to a hex digit");
+                        self.pc += 2;
+                    }
+                    // Fx30 - LD HF, Vx
+                    // Set I = location of the large sprite for digit Vx.
+                    0x0030 if self.mode != Mode::Chip8 => {
+                        self.I = Self::large_sprite_address(self.V[x as usize] % 10)
+                            .expect("reduced to a decimal digit");
                         self.pc += 2;
                     }
                     // Fx33 - LD B, Vx
                     // Store BCD representation of Vx in memory locations I, I+1, and I+2.
                     0x0033 => {
                         let vx = self.V[x as usize];
+                        let range = self.memory_range(self.I as usize, 3)?;
 
-                        self.memory[self.I as usize] = vx / 100;
-                        self.memory[(self.I + 1) as usize] = (vx / 10) % 10;
-                        self.memory[(self.I + 2) as usize] = vx % 10;
+                        self.memory[range].copy_from_slice(&[vx / 100, (vx / 10) % 10, vx % 10]);
+                        self.pc += 2;
+                    }
+                    // Fx3A - LD PITCH, Vx
+                    // Set the audio pattern playback rate to 4000 * 2^((Vx - 64) / 48) Hz.
+                    0x003A if self.mode == Mode::XoChip => {
+                        self.audio_pitch = self.V[x as usize];
                         self.pc += 2;
                     }
                     // Fx55 - LD [I], Vx
                     // Store registers V0 through Vx in memory starting at location I.
                     0x0055 => {
-                        for i in 0..=x as u16 {
-                            self.memory[(self.I + i) as usize] = self.V[x as usize];
+                        let range = self.memory_range(self.I as usize, x as usize + 1)?;
+                        self.memory[range].copy_from_slice(&self.V[..=x as usize]);
+                        if self.quirks.memory_increment {
+                            self.I = self.I.wrapping_add(x as u16 + 1);
                         }
                         self.pc += 2;
                     }
                     // Fx65 - LD Vx, [I]
                     // Read registers V0 through Vx from memory starting at location I.
                     0x0065 => {
-                        for i in 0..=x as u16 {
-                            self.V[x as usize] = self.memory[(self.I + i) as usize];
+                        let range = self.memory_range(self.I as usize, x as usize + 1)?;
+                        self.V[..=x as usize].copy_from_slice(&self.memory[range]);
+                        if self.quirks.memory_increment {
+                            self.I = self.I.wrapping_add(x as u16 + 1);
                         }
                         self.pc += 2;
                     }
-                    _ => unreachable!(),
+                    // Fx75 - LD R, Vx
+                    // Store registers V0 through Vx in the RPL user flags, x <= 7.
+                    0x0075 if self.mode != Mode::Chip8 => {
+                        let x = (x as usize).min(7);
+                        self.rpl_flags[..=x].copy_from_slice(&self.V[..=x]);
+                        self.pc += 2;
+                    }
+                    // Fx85 - LD Vx, R
+                    // Read registers V0 through Vx from the RPL user flags, x <= 7.
+                    0x0085 if self.mode != Mode::Chip8 => {
+                        let x = (x as usize).min(7);
+                        self.V[..=x].copy_from_slice(&self.rpl_flags[..=x]);
+                        self.pc += 2;
+                    }
+                    _ => return Err(CpuError::UnknownOpcode(self.pc, opcode)),
                 }
             }
-            _ => unreachable!(),
+            _ => return Err(CpuError::UnknownOpcode(self.pc, opcode)),
         }
+        Ok(())
     }
 }
 
@@ -509,3 +1279,721 @@ impl Default for Chip8 {
         Self::new()
     }
 }
+
+impl TryFrom<&[u8]> for Chip8 {
+    type Error = Chip8Error;
+
+    fn try_from(rom: &[u8]) -> Result<Self, Self::Error> {
+        let mut cpu = Self::new();
+        cpu.load_bytes(rom)?;
+        Ok(cpu)
+    }
+}
+
+impl TryFrom<Vec<u8>> for Chip8 {
+    type Error = Chip8Error;
+
+    fn try_from(rom: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::try_from(rom.as_slice())
+    }
+}
+
+impl TryFrom<&Path> for Chip8 {
+    type Error = Chip8Error;
+
+    fn try_from(path: &Path) -> Result<Self, Self::Error> {
+        Self::try_from(std::fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes `op` at pc and runs it
+    fn run_opcode(cpu: &mut Chip8, op: u16) {
+        let pc = cpu.pc as usize;
+        cpu.memory[pc..(pc + 2)].copy_from_slice(&op.to_be_bytes());
+        let _ = cpu.tick();
+    }
+
+    struct Case {
+        mode: Mode,
+        op: u16,
+        setup: fn(&mut Chip8),
+        check: fn(&Chip8) -> bool,
+    }
+
+    const fn case(mode: Mode, op: u16, setup: fn(&mut Chip8), check: fn(&Chip8) -> bool) -> Case {
+        Case {
+            mode,
+            op,
+            setup,
+            check,
+        }
+    }
+
+    fn unknown(cpu: &Chip8) -> bool {
+        cpu.pc == 0x200 && matches!(cpu.halted, Some(CpuError::UnknownOpcode(0x200, _)))
+    }
+
+    #[test]
+    fn every_opcode() {
+        let cases = [
+            // 0nnn
+            case(
+                Mode::Chip8,
+                0x00E0,
+                |cpu| cpu.gfx.set(0, 0, true),
+                |cpu| !cpu.gfx.get(0, 0) && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0x00EE,
+                |cpu| {
+                    cpu.stack[0] = 0x300;
+                    cpu.sp = 1;
+                },
+                |cpu| cpu.pc == 0x302 && cpu.sp == 0,
+            ),
+            case(
+                Mode::Chip8,
+                0x00EE,
+                |_| {},
+                |cpu| cpu.halted == Some(CpuError::StackUnderflow(0x200)),
+            ),
+            case(
+                Mode::XoChip,
+                0x00C1,
+                |cpu| cpu.gfx.set(0, 0, true),
+                |cpu| !cpu.gfx.get(0, 0) && cpu.gfx.get(0, 1) && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::XoChip,
+                0x00D1,
+                |cpu| cpu.gfx.set(0, 1, true),
+                |cpu| cpu.gfx.get(0, 0) && !cpu.gfx.get(0, 1) && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::XoChip,
+                0x00FB,
+                |cpu| cpu.gfx.set(0, 0, true),
+                |cpu| cpu.gfx.get(4, 0) && !cpu.gfx.get(0, 0) && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::XoChip,
+                0x00FC,
+                |cpu| cpu.gfx.set(4, 0, true),
+                |cpu| cpu.gfx.get(0, 0) && !cpu.gfx.get(4, 0) && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::SuperChip,
+                0x00FD,
+                |_| {},
+                |cpu| cpu.pc == 0x200 && cpu.halted == Some(CpuError::Exited(0x200)),
+            ),
+            case(
+                Mode::SuperChip,
+                0x00FE,
+                |cpu| cpu.gfx.resize(128, 64),
+                |cpu| cpu.gfx.width() == 64 && cpu.gfx.height() == 32 && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::SuperChip,
+                0x00FF,
+                |_| {},
+                |cpu| cpu.gfx.width() == 128 && cpu.gfx.height() == 64 && cpu.pc == 0x202,
+            ),
+            case(Mode::Chip8, 0x0123, |_| {}, unknown),
+            case(Mode::Chip8, 0x00C1, |_| {}, unknown),
+            case(Mode::SuperChip, 0x00D1, |_| {}, unknown),
+            case(Mode::Chip8, 0x00FB, |_| {}, unknown),
+            case(Mode::Chip8, 0x00FC, |_| {}, unknown),
+            case(Mode::Chip8, 0x00FD, |_| {}, unknown),
+            case(Mode::Chip8, 0x00FE, |_| {}, unknown),
+            case(Mode::Chip8, 0x00FF, |_| {}, unknown),
+            // 1nnn, 2nnn
+            case(Mode::Chip8, 0x1345, |_| {}, |cpu| cpu.pc == 0x345),
+            case(
+                Mode::Chip8,
+                0x2345,
+                |_| {},
+                |cpu| cpu.pc == 0x345 && cpu.sp == 1 && cpu.stack[0] == 0x200,
+            ),
+            case(
+                Mode::Chip8,
+                0x2345,
+                |cpu| cpu.sp = 16,
+                |cpu| cpu.halted == Some(CpuError::StackOverflow(0x200)),
+            ),
+            // 3xkk, 4xkk, 5xy0, 9xy0
+            case(
+                Mode::Chip8,
+                0x3142,
+                |cpu| cpu.V[1] = 0x42,
+                |cpu| cpu.pc == 0x204,
+            ),
+            case(
+                Mode::Chip8,
+                0x3143,
+                |cpu| cpu.V[1] = 0x42,
+                |cpu| cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0x4142,
+                |cpu| cpu.V[1] = 0x42,
+                |cpu| cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0x4143,
+                |cpu| cpu.V[1] = 0x42,
+                |cpu| cpu.pc == 0x204,
+            ),
+            case(
+                Mode::Chip8,
+                0x5120,
+                |cpu| cpu.V[1..3].copy_from_slice(&[5, 5]),
+                |cpu| cpu.pc == 0x204,
+            ),
+            case(
+                Mode::Chip8,
+                0x5120,
+                |cpu| cpu.V[1..3].copy_from_slice(&[5, 6]),
+                |cpu| cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0x9120,
+                |cpu| cpu.V[1..3].copy_from_slice(&[5, 5]),
+                |cpu| cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0x9120,
+                |cpu| cpu.V[1..3].copy_from_slice(&[5, 6]),
+                |cpu| cpu.pc == 0x204,
+            ),
+            // 5xy2, 5xy3
+            case(
+                Mode::XoChip,
+                0x5132,
+                |cpu| {
+                    cpu.I = 0x300;
+                    cpu.V[1..4].copy_from_slice(&[1, 2, 3]);
+                },
+                |cpu| cpu.memory[0x300..0x303] == [1, 2, 3] && cpu.I == 0x300,
+            ),
+            case(
+                Mode::XoChip,
+                0x5312,
+                |cpu| {
+                    cpu.I = 0x300;
+                    cpu.V[1..4].copy_from_slice(&[1, 2, 3]);
+                },
+                |cpu| cpu.memory[0x300..0x303] == [3, 2, 1],
+            ),
+            case(
+                Mode::XoChip,
+                0x5133,
+                |cpu| {
+                    cpu.I = 0x300;
+                    cpu.memory[0x300..0x303].copy_from_slice(&[7, 8, 9]);
+                },
+                |cpu| cpu.V[1..4] == [7, 8, 9] && cpu.pc == 0x202,
+            ),
+            case(Mode::Chip8, 0x5132, |_| {}, unknown),
+            case(Mode::SuperChip, 0x5133, |_| {}, unknown),
+            case(Mode::XoChip, 0x5121, |_| {}, unknown),
+            // 6xkk, 7xkk
+            case(
+                Mode::Chip8,
+                0x61AB,
+                |_| {},
+                |cpu| cpu.V[1] == 0xAB && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0x71FF,
+                |cpu| cpu.V[1] = 2,
+                |cpu| cpu.V[1] == 1 && cpu.V[0xF] == 0,
+            ),
+            // 8xyn
+            case(
+                Mode::Chip8,
+                0x8120,
+                |cpu| cpu.V[2] = 9,
+                |cpu| cpu.V[1] == 9 && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0x8121,
+                |cpu| cpu.V[1..3].copy_from_slice(&[0b1100, 0b1010]),
+                |cpu| cpu.V[1] == 0b1110,
+            ),
+            case(
+                Mode::Chip8,
+                0x8122,
+                |cpu| cpu.V[1..3].copy_from_slice(&[0b1100, 0b1010]),
+                |cpu| cpu.V[1] == 0b1000,
+            ),
+            case(
+                Mode::Chip8,
+                0x8123,
+                |cpu| cpu.V[1..3].copy_from_slice(&[0b1100, 0b1010]),
+                |cpu| cpu.V[1] == 0b0110,
+            ),
+            case(
+                Mode::Chip8,
+                0x8124,
+                |cpu| cpu.V[1..3].copy_from_slice(&[200, 100]),
+                |cpu| cpu.V[1] == 44 && cpu.V[0xF] == 1,
+            ),
+            case(
+                Mode::Chip8,
+                0x8125,
+                |cpu| cpu.V[1..3].copy_from_slice(&[5, 7]),
+                |cpu| cpu.V[1] == 254 && cpu.V[0xF] == 0,
+            ),
+            case(
+                Mode::Chip8,
+                0x8126,
+                |cpu| cpu.V[1] = 5,
+                |cpu| cpu.V[1] == 2 && cpu.V[0xF] == 1,
+            ),
+            case(
+                Mode::Chip8,
+                0x8127,
+                |cpu| cpu.V[1..3].copy_from_slice(&[5, 7]),
+                |cpu| cpu.V[1] == 2 && cpu.V[0xF] == 1,
+            ),
+            case(
+                Mode::Chip8,
+                0x812E,
+                |cpu| cpu.V[1] = 0x81,
+                |cpu| cpu.V[1] == 2 && cpu.V[0xF] == 1,
+            ),
+            case(Mode::Chip8, 0x8128, |_| {}, unknown),
+            // Annn, Bnnn, Cxkk
+            case(
+                Mode::Chip8,
+                0xA345,
+                |_| {},
+                |cpu| cpu.I == 0x345 && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0xB300,
+                |cpu| cpu.V[0] = 4,
+                |cpu| cpu.pc == 0x304,
+            ),
+            case(
+                Mode::Chip8,
+                0xC10F,
+                |cpu| cpu.V[1] = 0xFF,
+                |cpu| cpu.V[1] & 0xF0 == 0 && cpu.pc == 0x202,
+            ),
+            // Dxyn, drawing the 0 from the font
+            case(
+                Mode::Chip8,
+                0xD015,
+                |_| {},
+                |cpu| {
+                    cpu.gfx.get(0, 0)
+                        && cpu.gfx.get(3, 0)
+                        && cpu.gfx.get(0, 1)
+                        && !cpu.gfx.get(1, 1)
+                        && cpu.V[0xF] == 0
+                        && cpu.pc == 0x202
+                },
+            ),
+            case(
+                Mode::Chip8,
+                0xD015,
+                |cpu| cpu.gfx.set(0, 0, true),
+                |cpu| !cpu.gfx.get(0, 0) && cpu.V[0xF] == 1,
+            ),
+            // Exnn
+            case(
+                Mode::Chip8,
+                0xE19E,
+                |cpu| {
+                    cpu.V[1] = 3;
+                    cpu.key_states[3] = true;
+                },
+                |cpu| cpu.pc == 0x204,
+            ),
+            case(
+                Mode::Chip8,
+                0xE19E,
+                |cpu| cpu.V[1] = 3,
+                |cpu| cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0xE1A1,
+                |cpu| {
+                    cpu.V[1] = 3;
+                    cpu.key_states[3] = true;
+                },
+                |cpu| cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0xE1A1,
+                |cpu| cpu.V[1] = 3,
+                |cpu| cpu.pc == 0x204,
+            ),
+            case(Mode::Chip8, 0xE1FF, |_| {}, unknown),
+            // Fxnn
+            case(
+                Mode::XoChip,
+                0xF000,
+                |cpu| cpu.memory[0x202..0x204].copy_from_slice(&[0x12, 0x34]),
+                |cpu| cpu.I == 0x1234 && cpu.pc == 0x204,
+            ),
+            case(
+                Mode::XoChip,
+                0xF201,
+                |_| {},
+                |cpu| cpu.plane_mask == 2 && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::XoChip,
+                0xF002,
+                |cpu| {
+                    cpu.I = 0x300;
+                    cpu.memory[0x300..0x310].copy_from_slice(&[0xAA; 16]);
+                },
+                |cpu| cpu.audio_buffer == [0xAA; 16] && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0xF107,
+                |cpu| cpu.delay_timer = 9,
+                |cpu| cpu.V[1] == 9,
+            ),
+            case(Mode::Chip8, 0xF10A, |_| {}, |cpu| cpu.pc == 0x200),
+            case(
+                Mode::Chip8,
+                0xF10A,
+                |cpu| {
+                    cpu.key_states[5] = true;
+                },
+                |cpu| cpu.pc == 0x200,
+            ),
+            case(
+                Mode::Chip8,
+                0xF10A,
+                |cpu| cpu.key_presses[5] = true,
+                |cpu| cpu.V[1] == 5 && !cpu.key_presses[5] && cpu.pc == 0x202,
+            ),
+            case(
+                Mode::Chip8,
+                0xF115,
+                |cpu| cpu.V[1] = 7,
+                |cpu| cpu.delay_timer == 7,
+            ),
+            case(
+                Mode::Chip8,
+                0xF118,
+                |cpu| cpu.V[1] = 7,
+                |cpu| cpu.sound_timer == 7 && cpu.sound_active,
+            ),
+            case(
+                Mode::Chip8,
+                0xF11E,
+                |cpu| {
+                    cpu.I = 0x10;
+                    cpu.V[1] = 5;
+                },
+                |cpu| cpu.I == 0x15,
+            ),
+            case(Mode::Chip8, 0xF129, |cpu| cpu.V[1] = 0xA, |cpu| cpu.I == 50),
+            case(
+                Mode::SuperChip,
+                0xF130,
+                |cpu| cpu.V[1] = 3,
+                |cpu| cpu.I == LARGE_FONT_ADDRESS as u16 + 30,
+            ),
+            case(
+                Mode::Chip8,
+                0xF133,
+                |cpu| {
+                    cpu.I = 0x300;
+                    cpu.V[1] = 137;
+                },
+                |cpu| cpu.memory[0x300..0x303] == [1, 3, 7],
+            ),
+            case(
+                Mode::XoChip,
+                0xF13A,
+                |cpu| cpu.V[1] = 80,
+                |cpu| cpu.audio_pitch == 80,
+            ),
+            case(
+                Mode::Chip8,
+                0xF255,
+                |cpu| {
+                    cpu.I = 0x300;
+                    cpu.V[..3].copy_from_slice(&[1, 2, 3]);
+                },
+                |cpu| cpu.memory[0x300..0x303] == [1, 2, 3] && cpu.I == 0x300,
+            ),
+            case(
+                Mode::Chip8,
+                0xF265,
+                |cpu| {
+                    cpu.I = 0x300;
+                    cpu.memory[0x300..0x303].copy_from_slice(&[1, 2, 3]);
+                },
+                |cpu| cpu.V[..3] == [1, 2, 3] && cpu.I == 0x300,
+            ),
+            case(
+                Mode::SuperChip,
+                0xF275,
+                |cpu| cpu.V[..3].copy_from_slice(&[1, 2, 3]),
+                |cpu| cpu.rpl_flags[..3] == [1, 2, 3],
+            ),
+            case(
+                Mode::SuperChip,
+                0xF285,
+                |cpu| cpu.rpl_flags[..3].copy_from_slice(&[1, 2, 3]),
+                |cpu| cpu.V[..3] == [1, 2, 3],
+            ),
+            case(Mode::SuperChip, 0xF000, |_| {}, unknown),
+            case(Mode::Chip8, 0xF201, |_| {}, unknown),
+            case(Mode::SuperChip, 0xF002, |_| {}, unknown),
+            case(Mode::Chip8, 0xF130, |_| {}, unknown),
+            case(Mode::SuperChip, 0xF13A, |_| {}, unknown),
+            case(Mode::Chip8, 0xF275, |_| {}, unknown),
+            case(Mode::Chip8, 0xF285, |_| {}, unknown),
+            case(Mode::Chip8, 0xF1FF, |_| {}, unknown),
+        ];
+
+        for case in cases {
+            let mut cpu = Chip8::with_seed(0);
+            cpu.mode = case.mode;
+            (case.setup)(&mut cpu);
+            run_opcode(&mut cpu, case.op);
+            assert!(
+                (case.check)(&cpu),
+                "{:04x} ({}) in {}",
+                case.op,
+                Chip8::decode_instruction(&case.op),
+                case.mode.name()
+            );
+        }
+    }
+
+    #[test]
+    fn skips_step_over_long_load() {
+        // SE V0, 00; SNE V0, 01; SE V0, V0; SNE V0, V1; SKNP V0 all skip an F000 nnnn whole
+        for op in [0x3000, 0x4001, 0x5000, 0x9010, 0xE0A1] {
+            for (mode, pc) in [(Mode::XoChip, 0x206), (Mode::SuperChip, 0x204)] {
+                let mut cpu = Chip8::with_seed(0);
+                cpu.mode = mode;
+                cpu.V[1] = 1;
+                cpu.memory[0x202..0x204].copy_from_slice(&[0xF0, 0x00]);
+                run_opcode(&mut cpu, op);
+                assert_eq!(cpu.pc, pc, "{:04x} in {}", op, mode.name());
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_every_pair() {
+        let mut cpu = Chip8::with_seed(0);
+        for vx in 0..=255u8 {
+            for vy in 0..=255u8 {
+                let (sum, carry) = vx.overflowing_add(vy);
+                let (diff, borrow) = vx.overflowing_sub(vy);
+                let (diffn, borrown) = vy.overflowing_sub(vx);
+                for (op, result, flag) in [
+                    (0x8124, sum, carry as u8),
+                    (0x8125, diff, !borrow as u8),
+                    (0x8127, diffn, !borrown as u8),
+                    (0x8126, vx >> 1, vx & 1),
+                    (0x812E, vx << 1, vx >> 7),
+                ] {
+                    cpu.pc = 0x200;
+                    cpu.V[1] = vx;
+                    cpu.V[2] = vy;
+                    cpu.V[0xF] = 0xAA;
+                    run_opcode(&mut cpu, op);
+                    assert_eq!(
+                        (cpu.V[1], cpu.V[0xF]),
+                        (result, flag),
+                        "{:04x} with V1={} V2={}",
+                        op,
+                        vx,
+                        vy
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn font_sprites() {
+        let cpu = Chip8::new();
+        for digit in 0..=0xF {
+            let start = digit as usize * 5;
+            assert_eq!(
+                cpu.get_sprite_at(digit).unwrap()[..],
+                CHARACTER_SPRITES[start..(start + 5)],
+                "{:x}",
+                digit
+            );
+        }
+        for digit in 0..=9 {
+            let start = digit as usize * 10;
+            assert_eq!(
+                cpu.get_large_sprite_at(digit).unwrap()[..],
+                LARGE_CHARACTER_SPRITES[start..(start + 10)],
+                "{}",
+                digit
+            );
+        }
+        assert_eq!(Chip8::sprite_address(0x10), None);
+        assert!(cpu.get_sprite_at(0x10).is_none());
+        assert_eq!(Chip8::large_sprite_address(10), None);
+        assert!(cpu.get_large_sprite_at(10).is_none());
+    }
+
+    #[test]
+    fn shift_vf_flag_replaces_result() {
+        let mut cpu = Chip8::new();
+        cpu.V[0xF] = 0b10101010;
+        run_opcode(&mut cpu, 0x8FF6);
+        assert_eq!(cpu.V[0xF], 0);
+    }
+
+    #[test]
+    fn seeded_rnd() {
+        let expected = SmallRng::seed_from_u64(7).gen::<u8>();
+        let mut cpu = Chip8::with_seed(7);
+        run_opcode(&mut cpu, 0xC1FF);
+        assert_eq!(cpu.V[1], expected);
+
+        // A clone carries on with the same numbers
+        let mut clone = cpu.clone();
+        run_opcode(&mut cpu, 0xC2FF);
+        run_opcode(&mut clone, 0xC2FF);
+        assert_eq!(cpu.V[2], clone.V[2]);
+    }
+
+    #[test]
+    fn memory_checksum_clamps() {
+        let cpu = Chip8::new();
+        assert_eq!(
+            cpu.memory_checksum(0xF00, 0xFFFF),
+            cpu.memory_checksum(0xF00, 0x1000)
+        );
+        assert_eq!(cpu.memory_checksum(0x300, 0x200), crc32fast::hash(&[]));
+    }
+
+    #[test]
+    fn registers_round_trip_through_memory() {
+        for memory_increment in [false, true] {
+            let mut cpu = Chip8::new();
+            cpu.quirks.memory_increment = memory_increment;
+            cpu.I = 0x300;
+            cpu.V[..3].copy_from_slice(&[0x01, 0x02, 0x03]);
+            run_opcode(&mut cpu, 0xF255);
+            assert_eq!(cpu.memory[0x300..0x304], [0x01, 0x02, 0x03, 0x00]);
+
+            cpu.V = [0; 16];
+            cpu.I = 0x300;
+            run_opcode(&mut cpu, 0xF265);
+            assert_eq!(cpu.V[..4], [0x01, 0x02, 0x03, 0x00]);
+            assert_eq!(cpu.I, if memory_increment { 0x303 } else { 0x300 });
+        }
+    }
+
+    #[test]
+    fn load_bytes_runs_program() {
+        let mut cpu = Chip8::new();
+        // A longer ROM first, to check the rest of memory is cleared
+        cpu.load_bytes(&[0x60, 0x05, 0x70, 0x03, 0xAA, 0xBB])
+            .unwrap();
+        // LD V0, 05; ADD V0, 03
+        cpu.load_bytes(&[0x60, 0x05, 0x70, 0x03]).unwrap();
+        assert_eq!(cpu.memory[0x204..0x206], [0, 0]);
+
+        let _ = cpu.tick();
+        let _ = cpu.tick();
+        assert_eq!((cpu.V[0], cpu.pc), (8, 0x204));
+
+        assert!(cpu.load_bytes(&[1; MAX_ROM_SIZE]).is_ok());
+        assert!(matches!(
+            cpu.load_bytes(&[1; MAX_ROM_SIZE + 1]),
+            Err(Chip8Error::RomTooLarge(size)) if size == MAX_ROM_SIZE + 1
+        ));
+    }
+
+    #[test]
+    fn ffff_does_not_panic() {
+        assert_eq!(Chip8::decode_instruction(&0xFFFF), "UNK ffff");
+
+        let mut cpu = Chip8::new();
+        run_opcode(&mut cpu, 0xFFFF);
+        assert_eq!(cpu.halted, Some(CpuError::UnknownOpcode(0x200, 0xFFFF)));
+        // Further ticks do nothing until the halt is cleared
+        assert_eq!(cpu.tick(), Ok(()));
+        assert_eq!(cpu.pc, 0x200);
+    }
+
+    #[test]
+    fn vf_keeps_result_quirk() {
+        // VF + VF = 0x120, VF - V1 = 0x80, V1 - VF = 0x80
+        for (op, flag, result) in [(0x8FF4, 1, 0x20), (0x8F15, 1, 0x80), (0x8F17, 0, 0x80)] {
+            for vf_keeps_result in [false, true] {
+                let mut cpu = Chip8::new();
+                cpu.quirks.vf_keeps_result = vf_keeps_result;
+                cpu.V[0xF] = 0x90;
+                cpu.V[1] = 0x10;
+                run_opcode(&mut cpu, op);
+                let expected = if vf_keeps_result { result } else { flag };
+                assert_eq!(cpu.V[0xF], expected, "{:04x}", op);
+            }
+        }
+    }
+
+    #[test]
+    fn search_memory_wraps() {
+        let mut memory = [0u8; 4096];
+        memory[0x200..0x202].copy_from_slice(&[0xA2, 0x00]);
+        memory[0x300..0x304].copy_from_slice(&[0xA2, 0x00, 0xD0, 0x15]);
+        memory[0xFFE..].copy_from_slice(&[0x12, 0x34]);
+
+        let search = |pattern: &[u8], from| Chip8::search_memory(&memory, pattern, from);
+        assert_eq!(search(&[0xA2, 0x00, 0xD0, 0x15], 0), Some(0x300));
+        assert_eq!(search(&[0xA2, 0x00], 0), Some(0x200));
+        assert_eq!(search(&[0xA2, 0x00], 0x201), Some(0x300));
+        // Past the last match it carries on from the top
+        assert_eq!(search(&[0xA2, 0x00], 0x301), Some(0x200));
+        assert_eq!(search(&[0x12, 0x34], 0xFFF), Some(0xFFE));
+        assert_eq!(search(&[0x99], 0), None);
+        assert_eq!(search(&[], 0), None);
+    }
+
+    #[test]
+    fn program_input_does_not_panic() {
+        // Keys past 0xF use the low nibble
+        let mut cpu = Chip8::new();
+        cpu.V[1] = 0x13;
+        cpu.key_states[3] = true;
+        run_opcode(&mut cpu, 0xE19E);
+        assert_eq!(cpu.pc, 0x204);
+        run_opcode(&mut cpu, 0xE1A1);
+        assert_eq!(cpu.pc, 0x206);
+
+        let mut cpu = Chip8::new();
+        cpu.I = 0xFFFF;
+        cpu.V[1] = 2;
+        run_opcode(&mut cpu, 0xF11E);
+        assert_eq!((cpu.I, cpu.halted), (1, None));
+    }
+}
@@ -0,0 +1,359 @@
+use std::{collections::HashMap, fmt};
+
+// Programs are assembled to run from the usual CHIP-8 load address
+const ORIGIN: u16 = 0x200;
+
+#[derive(Debug, Clone)]
+pub struct AssembleError {
+    pub line: usize, // 0-based line number in the source
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line + 1, self.message)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    V(u8),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    B,
+    HF,
+    R,
+    Audio,
+    Pitch,
+    Long,
+    Range(u8, u8), // Vx-Vy
+    Imm(u16),
+    Label(String),
+}
+
+impl Operand {
+    fn parse(s: &str) -> Result<Self, String> {
+        let upper = s.to_ascii_uppercase();
+        match upper.as_str() {
+            "I" => return Ok(Operand::I),
+            "[I]" => return Ok(Operand::IndirectI),
+            "DT" => return Ok(Operand::DT),
+            "ST" => return Ok(Operand::ST),
+            "K" => return Ok(Operand::K),
+            "F" => return Ok(Operand::F),
+            "B" => return Ok(Operand::B),
+            "HF" => return Ok(Operand::HF),
+            "R" => return Ok(Operand::R),
+            "AUDIO" => return Ok(Operand::Audio),
+            "PITCH" => return Ok(Operand::Pitch),
+            "LONG" => return Ok(Operand::Long),
+            _ => {}
+        }
+
+        if let Some(x) = register(&upper) {
+            return Ok(Operand::V(x));
+        }
+        if let Some((x, y)) = upper.split_once('-') {
+            if let (Some(x), Some(y)) = (register(x.trim()), register(y.trim())) {
+                return Ok(Operand::Range(x, y));
+            }
+        }
+
+        // Numbers are hex, matching the output of `decode_instruction`, so names
+        // that are also valid hex numbers cannot be used as labels
+        let digits = upper
+            .strip_prefix("0X")
+            .or_else(|| upper.strip_prefix('#'))
+            .unwrap_or(&upper);
+        if let Ok(value) = u16::from_str_radix(digits, 16) {
+            return Ok(Operand::Imm(value));
+        }
+
+        if is_label(s) {
+            Ok(Operand::Label(s.to_string()))
+        } else {
+            Err(format!("invalid operand `{}`", s))
+        }
+    }
+}
+
+fn register(upper: &str) -> Option<u8> {
+    match upper.strip_prefix('V') {
+        Some(x) if x.len() == 1 => u8::from_str_radix(x, 16).ok(),
+        _ => None,
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+struct Assembler {
+    output: Vec<u8>,
+    labels: HashMap<String, u16>,
+    fixups: Vec<(usize, usize, String)>, // (line, output offset, label)
+    errors: Vec<AssembleError>,
+}
+
+impl Assembler {
+    fn address(&mut self, line: usize, operand: &Operand) -> Result<u16, String> {
+        match operand {
+            Operand::Imm(nnn) if *nnn <= 0x0FFF => Ok(*nnn),
+            Operand::Imm(nnn) => Err(format!("address {:x} out of range", nnn)),
+            // Patched once every label is known
+            Operand::Label(label) => {
+                self.fixups.push((line, self.output.len(), label.clone()));
+                Ok(0)
+            }
+            _ => Err("expected an address".to_string()),
+        }
+    }
+
+    fn encode(&mut self, line: usize, mnemonic: &str, operands: &[Operand]) -> Result<u16, String> {
+        use Operand::*;
+
+        let opcode = match (mnemonic, operands) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("SCD", [n]) => 0x00C0 | nibble(n)?,
+            ("SCU", [n]) => 0x00D0 | nibble(n)?,
+            ("SCR", []) => 0x00FB,
+            ("SCL", []) => 0x00FC,
+            ("EXIT", []) => 0x00FD,
+            ("LOW", []) => 0x00FE,
+            ("HIGH", []) => 0x00FF,
+            ("SYS", [addr]) => self.address(line, addr)?,
+            ("JP", [addr]) => 0x1000 | self.address(line, addr)?,
+            ("JP", [V(0), addr]) => 0xB000 | self.address(line, addr)?,
+            ("CALL", [addr]) => 0x2000 | self.address(line, addr)?,
+            ("SE", [V(x), V(y)]) => 0x5000 | xy(*x, *y),
+            ("SE", [V(x), kk]) => 0x3000 | x_kk(*x, kk)?,
+            ("SNE", [V(x), V(y)]) => 0x9000 | xy(*x, *y),
+            ("SNE", [V(x), kk]) => 0x4000 | x_kk(*x, kk)?,
+            ("LD", [V(x), V(y)]) => 0x8000 | xy(*x, *y),
+            ("LD", [V(x), DT]) => 0xF007 | x_only(*x),
+            ("LD", [V(x), K]) => 0xF00A | x_only(*x),
+            ("LD", [V(x), IndirectI]) => 0xF065 | x_only(*x),
+            ("LD", [V(x), R]) => 0xF085 | x_only(*x),
+            ("LD", [V(x), kk]) => 0x6000 | x_kk(*x, kk)?,
+            // The address goes in the next word, e.g. with `.byte`
+            ("LD", [I, Long]) => 0xF000,
+            ("LD", [I, addr]) => 0xA000 | self.address(line, addr)?,
+            ("LD", [DT, V(x)]) => 0xF015 | x_only(*x),
+            ("LD", [ST, V(x)]) => 0xF018 | x_only(*x),
+            ("LD", [F, V(x)]) => 0xF029 | x_only(*x),
+            ("LD", [B, V(x)]) => 0xF033 | x_only(*x),
+            ("LD", [IndirectI, V(x)]) => 0xF055 | x_only(*x),
+            ("LD", [IndirectI, Range(x, y)]) => 0x5002 | xy(*x, *y),
+            ("LD", [Range(x, y), IndirectI]) => 0x5003 | xy(*x, *y),
+            ("LD", [Audio, IndirectI]) => 0xF002,
+            ("LD", [HF, V(x)]) => 0xF030 | x_only(*x),
+            ("LD", [Pitch, V(x)]) => 0xF03A | x_only(*x),
+            ("LD", [R, V(x)]) => 0xF075 | x_only(*x),
+            ("ADD", [V(x), V(y)]) => 0x8004 | xy(*x, *y),
+            ("ADD", [I, V(x)]) => 0xF01E | x_only(*x),
+            ("ADD", [V(x), kk]) => 0x7000 | x_kk(*x, kk)?,
+            ("OR", [V(x), V(y)]) => 0x8001 | xy(*x, *y),
+            ("AND", [V(x), V(y)]) => 0x8002 | xy(*x, *y),
+            ("XOR", [V(x), V(y)]) => 0x8003 | xy(*x, *y),
+            ("SUB", [V(x), V(y)]) => 0x8005 | xy(*x, *y),
+            ("SHR", [V(x)]) => 0x8006 | x_only(*x),
+            ("SHR", [V(x), V(y)]) => 0x8006 | xy(*x, *y),
+            ("SUBN", [V(x), V(y)]) => 0x8007 | xy(*x, *y),
+            ("SHL", [V(x)]) => 0x800E | x_only(*x),
+            ("SHL", [V(x), V(y)]) => 0x800E | xy(*x, *y),
+            ("RND", [V(x), kk]) => 0xC000 | x_kk(*x, kk)?,
+            ("DRW", [V(x), V(y), n]) => 0xD000 | xy(*x, *y) | nibble(n)?,
+            ("SKP", [V(x)]) => 0xE09E | x_only(*x),
+            ("SKNP", [V(x)]) => 0xE0A1 | x_only(*x),
+            ("PLN", [n]) => 0xF001 | nibble(n)? << 8,
+            _ => return Err(format!("invalid instruction `{}`", mnemonic)),
+        };
+        Ok(opcode)
+    }
+
+    fn assemble_line(&mut self, line: usize, text: &str) -> Result<(), String> {
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(format!("invalid label `{}`", label));
+            }
+            let address = ORIGIN + self.output.len() as u16;
+            if self.labels.insert(label.to_string(), address).is_some() {
+                return Err(format!("label `{}` defined twice", label));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(Operand::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Raw data, e.g. sprites: `.byte F0, 90, 90, 90, F0`
+        if mnemonic.eq_ignore_ascii_case(".byte") {
+            if operands.is_empty() {
+                return Err("expected at least one byte".to_string());
+            }
+            for operand in &operands {
+                self.output.push(byte(operand)?);
+            }
+            return Ok(());
+        }
+
+        let opcode = self.encode(line, &mnemonic.to_ascii_uppercase(), &operands)?;
+        self.output.extend_from_slice(&opcode.to_be_bytes());
+        Ok(())
+    }
+}
+
+fn x_only(x: u8) -> u16 {
+    (x as u16 & 0xF) << 8
+}
+
+fn xy(x: u8, y: u8) -> u16 {
+    x_only(x) | (y as u16 & 0xF) << 4
+}
+
+fn x_kk(x: u8, kk: &Operand) -> Result<u16, String> {
+    match kk {
+        Operand::Imm(kk) if *kk <= 0xFF => Ok(x_only(x) | kk),
+        Operand::Imm(kk) => Err(format!("byte {:x} out of range", kk)),
+        _ => Err("expected a byte".to_string()),
+    }
+}
+
+fn byte(kk: &Operand) -> Result<u8, String> {
+    match kk {
+        Operand::Imm(kk) if *kk <= 0xFF => Ok(*kk as u8),
+        Operand::Imm(kk) => Err(format!("byte {:x} out of range", kk)),
+        Operand::B => Ok(0xB),
+        Operand::F => Ok(0xF),
+        _ => Err("expected a byte".to_string()),
+    }
+}
+
+fn nibble(n: &Operand) -> Result<u16, String> {
+    match n {
+        Operand::Imm(n) if *n <= 0xF => Ok(*n),
+        // `B` and `F` are also valid hex nibbles
+        Operand::B => Ok(0xB),
+        Operand::F => Ok(0xF),
+        _ => Err("expected a nibble".to_string()),
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, Vec<AssembleError>> {
+    let mut assembler = Assembler {
+        output: Vec::new(),
+        labels: HashMap::new(),
+        fixups: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    for (line, text) in source.lines().enumerate() {
+        if let Err(message) = assembler.assemble_line(line, text) {
+            assembler.errors.push(AssembleError { line, message });
+        }
+    }
+
+    for (line, offset, label) in std::mem::take(&mut assembler.fixups) {
+        match assembler.labels.get(&label) {
+            Some(address) if *address > 0x0FFF => assembler.errors.push(AssembleError {
+                line,
+                message: format!("label `{}` at {:x} is out of range", label, address),
+            }),
+            Some(address) => {
+                let opcode =
+                    u16::from_be_bytes([assembler.output[offset], assembler.output[offset + 1]])
+                        | address;
+                assembler.output[offset..(offset + 2)].copy_from_slice(&opcode.to_be_bytes());
+            }
+            None => assembler.errors.push(AssembleError {
+                line,
+                message: format!("undefined label `{}`", label),
+            }),
+        }
+    }
+
+    if assembler.errors.is_empty() {
+        Ok(assembler.output)
+    } else {
+        Err(assembler.errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+
+    fn opcodes(rom: &[u8]) -> Vec<u16> {
+        rom.chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn decoded_instructions_assemble_back() {
+        for opcode in 0..=0xFFFF {
+            let text = Chip8::decode_instruction(&opcode);
+            if text.is_empty() || text.starts_with("UNK") {
+                continue;
+            }
+            // Opcodes the decoder aliases, like 9xy1 for SNE Vx, Vy, come back in their usual form
+            match assemble(&text) {
+                Ok(rom) => {
+                    let [assembled] = opcodes(&rom)[..] else {
+                        panic!("{} assembled to {:x?}", text, rom);
+                    };
+                    assert_eq!(
+                        Chip8::decode_instruction(&assembled),
+                        text,
+                        "{:04x}",
+                        opcode
+                    );
+                }
+                Err(errors) => panic!("{}: {}", text, errors[0]),
+            }
+        }
+    }
+
+    #[test]
+    fn labels() {
+        let rom = assemble("start: JP end\n CALL start\nend: LD I, start").unwrap();
+        assert_eq!(opcodes(&rom), [0x1204, 0x2200, 0xA200]);
+
+        let errors = assemble("JP nowhere").unwrap_err();
+        assert_eq!(errors[0].message, "undefined label `nowhere`");
+    }
+
+    #[test]
+    fn label_out_of_range() {
+        // 0xE00 bytes of padding put `end` at 0x1000, past the 12-bit address space
+        let source = format!(
+            "JP end\n.byte {}\nend: CLS",
+            vec!["0"; 0xE00 - 2].join(", ")
+        );
+        let errors = assemble(&source).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 0);
+        assert_eq!(errors[0].message, "label `end` at 1000 is out of range");
+    }
+}
@@ -0,0 +1,178 @@
+// How an interpreter dialect handles an opcode, compared to cchipt's default behaviour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    Supported,
+    UnsupportedIgnored,
+    UnsupportedCrash,
+    BehaviorDiffers,
+}
+
+pub struct CompatibilityCell {
+    pub compatibility: Compatibility,
+    pub note: &'static str,
+}
+
+pub struct CompatibilityRow {
+    pub opcode: &'static str,
+    pub mnemonic: &'static str,
+    pub description: &'static str,
+    pub cells: [CompatibilityCell; 3], // Indexed like `QuirksPreset::ALL`
+}
+
+pub struct CompatibilityMatrix {
+    pub rows: Vec<CompatibilityRow>,
+}
+
+use Compatibility::*;
+
+const SAME: (Compatibility, &str) = (Supported, "Behaves the same as cchipt's default.");
+const SYS_IGNORED: (Compatibility, &str) = (
+    UnsupportedIgnored,
+    "Calls a native machine code routine, which cannot be emulated and is skipped.",
+);
+const SCHIP_ONLY: (Compatibility, &str) = (
+    UnsupportedCrash,
+    "Not part of this dialect. The interpreter treats it as a machine code call and crashes.",
+);
+const XO_ONLY: (Compatibility, &str) = (
+    UnsupportedCrash,
+    "An XO-CHIP extension that this dialect does not have. The interpreter crashes on it.",
+);
+
+fn row(
+    opcode: &'static str,
+    mnemonic: &'static str,
+    description: &'static str,
+    cells: [(Compatibility, &'static str); 3],
+) -> CompatibilityRow {
+    CompatibilityRow {
+        opcode,
+        mnemonic,
+        description,
+        cells: cells.map(|(compatibility, note)| CompatibilityCell {
+            compatibility,
+            note,
+        }),
+    }
+}
+
+impl CompatibilityMatrix {
+    pub fn new() -> Self {
+        let rows = vec![
+            row("00E0", "CLS", "Clear the display.", [SAME, SAME, SAME]),
+            row("00EE", "RET", "Return from a subroutine.", [SAME, SAME, SAME]),
+            row("0nnn", "SYS addr", "Jump to a machine code routine at nnn.", [SYS_IGNORED, SYS_IGNORED, SYS_IGNORED]),
+            row("00Cn", "SCD n", "Scroll the display down by n rows.", [SCHIP_ONLY, SCHIP_ONLY, SAME]),
+            row("00Dn", "SCU n", "Scroll the display up by n rows.", [XO_ONLY, XO_ONLY, XO_ONLY]),
+            row("00FB", "SCR", "Scroll the display right by 4 pixels.", [SCHIP_ONLY, SCHIP_ONLY, SAME]),
+            row("00FC", "SCL", "Scroll the display left by 4 pixels.", [SCHIP_ONLY, SCHIP_ONLY, SAME]),
+            row("00FD", "EXIT", "Exit the interpreter.", [SCHIP_ONLY, SCHIP_ONLY, SAME]),
+            row("00FE", "LOW", "Switch to 64x32 low resolution mode.", [SCHIP_ONLY, SCHIP_ONLY, SAME]),
+            row("00FF", "HIGH", "Switch to 128x64 high resolution mode.", [SCHIP_ONLY, SCHIP_ONLY, SAME]),
+            row("1nnn", "JP addr", "Jump to location nnn.", [SAME, SAME, SAME]),
+            row("2nnn", "CALL addr", "Call subroutine at nnn.", [SAME, SAME, SAME]),
+            row("3xkk", "SE Vx, byte", "Skip next instruction if Vx = kk.", [SAME, SAME, SAME]),
+            row("4xkk", "SNE Vx, byte", "Skip next instruction if Vx != kk.", [SAME, SAME, SAME]),
+            row("5xy0", "SE Vx, Vy", "Skip next instruction if Vx = Vy.", [SAME, SAME, SAME]),
+            row(
+                "5xy2/3",
+                "LD [I], Vx-Vy / LD Vx-Vy, [I]",
+                "Store/read registers Vx through Vy at I, leaving I unchanged.",
+                [XO_ONLY, XO_ONLY, XO_ONLY],
+            ),
+            row("6xkk", "LD Vx, byte", "Set Vx = kk.", [SAME, SAME, SAME]),
+            row("7xkk", "ADD Vx, byte", "Set Vx = Vx + kk.", [SAME, SAME, SAME]),
+            row("8xy0", "LD Vx, Vy", "Set Vx = Vy.", [SAME, SAME, SAME]),
+            row(
+                "8xy1/2/3",
+                "OR/AND/XOR Vx, Vy",
+                "Set Vx = Vx OR/AND/XOR Vy.",
+                [(BehaviorDiffers, "VF is reset to 0 as a side effect."), SAME, SAME],
+            ),
+            row("8xy4", "ADD Vx, Vy", "Set Vx = Vx + Vy, set VF = carry.", [SAME, SAME, SAME]),
+            row(
+                "8xy5/7",
+                "SUB/SUBN Vx, Vy",
+                "Set Vx = Vx - Vy (or Vy - Vx), set VF = NOT borrow.",
+                [SAME, SAME, SAME],
+            ),
+            row(
+                "8xy6/E",
+                "SHR/SHL Vx {, Vy}",
+                "Set Vx = Vx SHR/SHL 1.",
+                [(BehaviorDiffers, "Vy is shifted and the result is stored in Vx."), SAME, SAME],
+            ),
+            row("9xy0", "SNE Vx, Vy", "Skip next instruction if Vx != Vy.", [SAME, SAME, SAME]),
+            row("Annn", "LD I, addr", "Set I = nnn.", [SAME, SAME, SAME]),
+            row(
+                "Bnnn",
+                "JP V0, addr",
+                "Jump to location nnn + V0.",
+                [
+                    SAME,
+                    (BehaviorDiffers, "Decoded as Bxnn and jumps to xnn + Vx."),
+                    (BehaviorDiffers, "Decoded as Bxnn and jumps to xnn + Vx."),
+                ],
+            ),
+            row("Cxkk", "RND Vx, byte", "Set Vx = random byte AND kk.", [SAME, SAME, SAME]),
+            row(
+                "Dxyn",
+                "DRW Vx, Vy, nibble",
+                "Display n-byte sprite at (Vx, Vy), set VF = collision.",
+                [
+                    (BehaviorDiffers, "Sprites are clipped at the screen edges and drawing waits for the vertical blank."),
+                    (BehaviorDiffers, "Sprites are clipped at the screen edges."),
+                    (BehaviorDiffers, "Sprites are clipped at the screen edges."),
+                ],
+            ),
+            row(
+                "Dxy0",
+                "DRW Vx, Vy, 0",
+                "Display a 16x16 sprite at (Vx, Vy).",
+                [
+                    (UnsupportedIgnored, "Draws a sprite of 0 bytes, which does nothing."),
+                    (UnsupportedIgnored, "Draws a sprite of 0 bytes, which does nothing."),
+                    SAME,
+                ],
+            ),
+            row("Ex9E", "SKP Vx", "Skip next instruction if key Vx is pressed.", [SAME, SAME, SAME]),
+            row("ExA1", "SKNP Vx", "Skip next instruction if key Vx is not pressed.", [SAME, SAME, SAME]),
+            row(
+                "F000 nnnn",
+                "LD I, LONG nnnn",
+                "Set I = nnnn, the 16-bit address in the next word. Skips step over all 4 bytes.",
+                [XO_ONLY, XO_ONLY, XO_ONLY],
+            ),
+            row("Fn01", "PLN n", "Select the bitplanes drawn to.", [XO_ONLY, XO_ONLY, XO_ONLY]),
+            row("F002", "LD AUDIO, [I]", "Load the 16-byte audio pattern at I.", [XO_ONLY, XO_ONLY, XO_ONLY]),
+            row("Fx07", "LD Vx, DT", "Set Vx = delay timer value.", [SAME, SAME, SAME]),
+            row("Fx0A", "LD Vx, K", "Wait for a key press, store the key in Vx.", [SAME, SAME, SAME]),
+            row("Fx15", "LD DT, Vx", "Set delay timer = Vx.", [SAME, SAME, SAME]),
+            row("Fx18", "LD ST, Vx", "Set sound timer = Vx.", [SAME, SAME, SAME]),
+            row("Fx1E", "ADD I, Vx", "Set I = I + Vx.", [SAME, SAME, SAME]),
+            row("Fx29", "LD F, Vx", "Set I = location of the small sprite for digit Vx.", [SAME, SAME, SAME]),
+            row("Fx30", "LD HF, Vx", "Set I = location of the large sprite for digit Vx.", [SCHIP_ONLY, SCHIP_ONLY, SAME]),
+            row("Fx33", "LD B, Vx", "Store BCD of Vx in I, I+1 and I+2.", [SAME, SAME, SAME]),
+            row("Fx3A", "LD PITCH, Vx", "Set the audio pattern playback rate from Vx.", [XO_ONLY, XO_ONLY, XO_ONLY]),
+            row(
+                "Fx55/65",
+                "LD [I], Vx / LD Vx, [I]",
+                "Store/read registers V0 through Vx at I.",
+                [
+                    (BehaviorDiffers, "I is incremented by x + 1."),
+                    (Supported, "I is left unchanged, the CHIP-48 preset does not emulate its increment by x."),
+                    SAME,
+                ],
+            ),
+            row("Fx75/85", "LD R, Vx / LD Vx, R", "Store/read V0 through Vx in the RPL user flags.", [SCHIP_ONLY, SCHIP_ONLY, SAME]),
+        ];
+
+        Self { rows }
+    }
+}
+
+impl Default for CompatibilityMatrix {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,58 @@
+use super::Chip8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Same,
+    Changed,
+    Added,
+    Removed,
+}
+
+pub struct RomDiffEntry {
+    pub address: u16,
+    pub opcode_a: Option<u16>,
+    pub opcode_b: Option<u16>,
+    pub mnemonic_a: String,
+    pub mnemonic_b: String,
+}
+
+impl RomDiffEntry {
+    pub fn kind(&self) -> DiffKind {
+        match (self.opcode_a, self.opcode_b) {
+            (Some(a), Some(b)) if a == b => DiffKind::Same,
+            (Some(_), Some(_)) => DiffKind::Changed,
+            (None, _) => DiffKind::Added,
+            (_, None) => DiffKind::Removed,
+        }
+    }
+}
+
+fn opcode_at(rom: &[u8], offset: usize) -> Option<u16> {
+    // A trailing odd byte is read as the high byte of an opcode
+    let high = *rom.get(offset)?;
+    let low = rom.get(offset + 1).copied().unwrap_or_default();
+    Some(u16::from_be_bytes([high, low]))
+}
+
+fn mnemonic(opcode: Option<u16>) -> String {
+    opcode
+        .map(|opcode| Chip8::decode_instruction(&opcode))
+        .unwrap_or_default()
+}
+
+pub fn diff_roms(rom_a: &[u8], rom_b: &[u8]) -> Vec<RomDiffEntry> {
+    (0..rom_a.len().max(rom_b.len()))
+        .step_by(2)
+        .map(|offset| {
+            let opcode_a = opcode_at(rom_a, offset);
+            let opcode_b = opcode_at(rom_b, offset);
+            RomDiffEntry {
+                address: 0x200 + offset as u16,
+                opcode_a,
+                opcode_b,
+                mnemonic_a: mnemonic(opcode_a),
+                mnemonic_b: mnemonic(opcode_b),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+
+use super::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// What scroll distances are measured in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollUnit {
+    Pixel,        // Pixels at the current resolution
+    HighResPixel, // 128x64 pixels, so the display moves half as far in low resolution
+}
+
+// Monochrome display, sized by the current resolution
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Row-major, `width * height` pixels
+    pub fn pixels(&self) -> &[bool] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        let index = self.index(x, y);
+        self.pixels[index] = on;
+    }
+
+    // A column past the width would otherwise land on the next row instead of panicking
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel ({}, {}) is outside the {}x{} display",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        y * self.width + x
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(false);
+    }
+
+    // Changing the resolution also clears the display
+    pub fn resize(&mut self, width: usize, height: usize) {
+        *self = Self::new(width, height);
+    }
+
+    fn rows(&self, n: usize, unit: ScrollUnit) -> usize {
+        match unit {
+            ScrollUnit::Pixel => n,
+            ScrollUnit::HighResPixel => n * self.height / 64,
+        }
+        .min(self.height)
+    }
+
+    fn cols(&self, n: usize, unit: ScrollUnit) -> usize {
+        match unit {
+            ScrollUnit::Pixel => n,
+            ScrollUnit::HighResPixel => n * self.width / 128,
+        }
+        .min(self.width)
+    }
+
+    pub fn scroll_down(&mut self, n: usize, unit: ScrollUnit) {
+        let rows = self.rows(n, unit);
+        let shifted = rows * self.width;
+        let kept = (self.height - rows) * self.width;
+        self.pixels.copy_within(..kept, shifted);
+        self.pixels[..shifted].fill(false);
+    }
+
+    pub fn scroll_up(&mut self, n: usize, unit: ScrollUnit) {
+        let rows = self.rows(n, unit);
+        let shifted = rows * self.width;
+        let kept = (self.height - rows) * self.width;
+        self.pixels.copy_within(shifted.., 0);
+        self.pixels[kept..].fill(false);
+    }
+
+    pub fn scroll_right(&mut self, n: usize, unit: ScrollUnit) {
+        let cols = self.cols(n, unit);
+        for row in self.pixels.chunks_exact_mut(self.width) {
+            row.rotate_right(cols);
+            row[..cols].fill(false);
+        }
+    }
+
+    pub fn scroll_left(&mut self, n: usize, unit: ScrollUnit) {
+        let cols = self.cols(n, unit);
+        for row in self.pixels.chunks_exact_mut(self.width) {
+            row.rotate_left(cols);
+            let kept = self.width - cols;
+            row[kept..].fill(false);
+        }
+    }
+
+    // XORs a sprite onto the display, `sprite_width` is 8 or 16 pixels. Returns whether any
+    // pixel was turned off
+    pub fn draw_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+        sprite_width: usize,
+        clip: bool,
+    ) -> bool {
+        let row_bytes = sprite_width / 8;
+        let mut collision = false;
+
+        for (row, bytes) in sprite.chunks_exact(row_bytes).enumerate() {
+            let bits = bytes
+                .iter()
+                .fold(0u16, |bits, byte| bits << 8 | *byte as u16);
+            for col in 0..sprite_width {
+                if bits & (1 << (sprite_width - 1 - col)) == 0 {
+                    continue;
+                }
+                let (px, py) = (col + x, row + y);
+                if clip && (px >= self.width || py >= self.height) {
+                    continue;
+                }
+                let (px, py) = (px % self.width, py % self.height);
+                let cur_val = self.get(px, py);
+                if cur_val {
+                    collision = true;
+                }
+                self.set(px, py, !cur_val);
+            }
+        }
+        collision
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new(SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize)
+    }
+}
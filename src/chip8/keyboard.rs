@@ -0,0 +1,97 @@
+use super::Chip8;
+
+// Scripted keypad input for driving a `Chip8` without a window, e.g. when checking the
+// key-sensitive opcodes (Ex9E, ExA1, Fx0A) in tests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MockKeyboard {
+    pub held: [bool; 16],
+    pub pressed: [bool; 16], // Went down since the last `apply_to`
+}
+
+impl MockKeyboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Keys above 0xF are ignored, like the real keypad only has 16
+    pub fn press(&mut self, key: u8) {
+        if let Some(held) = self.held.get_mut(key as usize) {
+            if !*held {
+                self.pressed[key as usize] = true;
+            }
+            *held = true;
+        }
+    }
+
+    pub fn release(&mut self, key: u8) {
+        if let Some(held) = self.held.get_mut(key as usize) {
+            *held = false;
+        }
+    }
+
+    // Hands the input over to the CPU the same way a frame of real input does
+    pub fn apply_to(&mut self, cpu: &mut Chip8) {
+        cpu.key_states = self.held;
+        for (cpu_pressed, pressed) in cpu.key_presses.iter_mut().zip(self.pressed) {
+            *cpu_pressed |= pressed;
+        }
+        self.pressed = [false; 16];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_opcodes() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x05, // LD   V0, 05
+            0xE0, 0x9E, // SKP  V0
+            0x61, 0x01, // LD   V1, 01
+            0xE0, 0xA1, // SKNP V0
+            0x62, 0x01, // LD   V2, 01
+            0xF3, 0x0A, // LD   V3, K
+        ];
+        let mut cpu = Chip8::try_from(&rom[..]).unwrap();
+        let mut keys = MockKeyboard::new();
+        keys.press(5);
+        keys.press(0x20);
+        keys.apply_to(&mut cpu);
+        assert!(cpu.key_states[5]);
+
+        for _ in 0..4 {
+            let _ = cpu.tick();
+        }
+        assert_eq!(cpu.V[1], 0); // Skipped, 5 is held
+        assert_eq!(cpu.V[2], 1); // Not skipped
+
+        // A new frame, 5 is still held but no longer counts as pressed
+        cpu.key_presses = [false; 16];
+        let _ = cpu.tick();
+        let _ = cpu.tick();
+        assert_eq!(cpu.pc, 0x20A);
+
+        keys.release(5);
+        keys.press(0xB);
+        keys.apply_to(&mut cpu);
+        assert!(!cpu.key_states[5]);
+        let _ = cpu.tick();
+        assert_eq!((cpu.V[3], cpu.pc), (0xB, 0x20C));
+    }
+
+    #[test]
+    fn held_key_is_pressed_once() {
+        let mut keys = MockKeyboard::new();
+        keys.press(3);
+        let mut cpu = Chip8::new();
+        keys.apply_to(&mut cpu);
+        assert!(cpu.key_presses[3]);
+
+        cpu.key_presses = [false; 16];
+        keys.press(3);
+        keys.apply_to(&mut cpu);
+        assert!(cpu.key_states[3] && !cpu.key_presses[3]);
+    }
+}
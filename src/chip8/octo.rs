@@ -0,0 +1,731 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+
+// Compiles a subset of Octo (https://github.com/JohnEarnest/Octo): labels, `:alias`, `:const`,
+// `:macro`, `:byte`, register arithmetic, `if`/`loop` control flow and sprite data written as
+// bare numbers. Like Octo the program starts with a jump to `main`
+
+const ORIGIN: u16 = 0x200;
+
+// Macros expanding macros this deep are taken to be recursive, which would never finish
+const MAX_MACRO_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct OctoError {
+    pub line: usize, // 0-based line number in the source
+    pub message: String,
+}
+
+impl fmt::Display for OctoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line + 1, self.message)
+    }
+}
+
+impl std::error::Error for OctoError {}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    depth: usize, // How many macro expansions produced it
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Register(u8),
+    Value(u8),
+}
+
+struct Condition {
+    x: u8,
+    comparison: String,
+    rhs: Option<Operand>, // `None` for `key` and `-key`
+}
+
+// Open `if ... begin` and `loop` blocks, with the offsets of jumps still to be patched
+enum Block {
+    If { jump: usize },
+    Else { jump: usize },
+    Loop { start: u16, whiles: Vec<usize> },
+}
+
+struct Compiler {
+    tokens: VecDeque<Token>,
+    line: usize,  // Line of the last token taken, where errors are reported
+    depth: usize, // Macro depth of the last token taken
+    rom: Vec<u8>,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, i32>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<(usize, usize, String)>, // (line, rom offset, label)
+    blocks: Vec<Block>,
+}
+
+fn tokenize(source: &str) -> VecDeque<Token> {
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(line, text)| {
+            let code = text.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |word| Token {
+                text: word.to_string(),
+                line,
+                depth: 0,
+            })
+        })
+        .collect()
+}
+
+fn parse_number(s: &str) -> Option<i32> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i32::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn parse_register(s: &str) -> Option<u8> {
+    let digit = s.strip_prefix(['v', 'V'])?;
+    match digit.len() {
+        1 => u8::from_str_radix(digit, 16).ok(),
+        _ => None,
+    }
+}
+
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+impl Compiler {
+    fn next(&mut self) -> Result<String, String> {
+        let token = self
+            .tokens
+            .pop_front()
+            .ok_or_else(|| "unexpected end of program".to_string())?;
+        self.line = token.line;
+        self.depth = token.depth;
+        Ok(token.text)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected `{}`, found `{}`", expected, token)),
+        }
+    }
+
+    fn here(&self) -> u16 {
+        ORIGIN + self.rom.len() as u16
+    }
+
+    fn inst(&mut self, opcode: u16) {
+        self.rom.extend_from_slice(&opcode.to_be_bytes());
+    }
+
+    // ORs an address into the jump at `offset`
+    fn patch(&mut self, offset: usize, address: u16) {
+        let opcode = u16::from_be_bytes([self.rom[offset], self.rom[offset + 1]]) | address;
+        self.rom[offset..(offset + 2)].copy_from_slice(&opcode.to_be_bytes());
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let name = self.next()?;
+        if !is_name(&name) || self.register_of(&name).is_some() {
+            return Err(format!("invalid name `{}`", name));
+        }
+        Ok(name)
+    }
+
+    fn register_of(&self, token: &str) -> Option<u8> {
+        parse_register(token).or_else(|| self.aliases.get(token).copied())
+    }
+
+    fn register(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        self.register_of(&token)
+            .ok_or_else(|| format!("expected a register, found `{}`", token))
+    }
+
+    fn value_of(&self, token: &str) -> Option<i32> {
+        parse_number(token).or_else(|| self.constants.get(token).copied())
+    }
+
+    fn value(&mut self) -> Result<i32, String> {
+        let token = self.next()?;
+        self.value_of(&token)
+            .ok_or_else(|| format!("expected a number, found `{}`", token))
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        self.byte_of(&token)
+    }
+
+    // Negative bytes wrap around, so `v0 += -1` decrements
+    fn byte_of(&self, token: &str) -> Result<u8, String> {
+        match self.value_of(token) {
+            Some(value @ -128..=255) => Ok(value as u8),
+            Some(value) => Err(format!("{} does not fit in a byte", value)),
+            None => Err(format!("expected a number, found `{}`", token)),
+        }
+    }
+
+    fn nibble(&mut self) -> Result<u16, String> {
+        match self.value()? {
+            value @ 0..=15 => Ok(value as u16),
+            value => Err(format!("{} does not fit in a nibble", value)),
+        }
+    }
+
+    // Labels can be used before they are defined, they are filled in at the end
+    fn address(&mut self) -> Result<u16, String> {
+        let token = self.next()?;
+        self.address_of(&token)
+    }
+
+    fn address_of(&mut self, token: &str) -> Result<u16, String> {
+        if let Some(address) = self.labels.get(token) {
+            return Ok(*address);
+        }
+        match self.value_of(token) {
+            Some(value @ 0..=0xFFF) => Ok(value as u16),
+            Some(value) => Err(format!("address {:x} out of range", value)),
+            None if is_name(token) => {
+                self.fixups
+                    .push((self.line, self.rom.len(), token.to_string()));
+                Ok(0)
+            }
+            None => Err(format!("expected an address, found `{}`", token)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.peek().and_then(|token| self.register_of(token)) {
+            Some(y) => {
+                self.next()?;
+                Ok(Operand::Register(y))
+            }
+            None => Ok(Operand::Value(self.byte()?)),
+        }
+    }
+
+    fn register_operand(&mut self) -> Result<u16, String> {
+        Ok((self.register()? as u16) << 4)
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let x = self.register()?;
+        let comparison = self.next()?;
+        let rhs = match comparison.as_str() {
+            "key" | "-key" => None,
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => Some(self.operand()?),
+            _ => return Err(format!("invalid comparison `{}`", comparison)),
+        };
+        Ok(Condition { x, comparison, rhs })
+    }
+
+    // Skips the next instruction unless the condition holds, or if it holds when `negated`
+    fn skip_unless(&mut self, condition: &Condition, negated: bool) {
+        let comparison = match (condition.comparison.as_str(), negated) {
+            (comparison, false) => comparison,
+            ("==", true) => "!=",
+            ("!=", true) => "==",
+            ("key", true) => "-key",
+            ("-key", true) => "key",
+            ("<", true) => ">=",
+            (">=", true) => "<",
+            (">", true) => "<=",
+            (_, true) => ">",
+        };
+        let x = (condition.x as u16) << 8;
+        let vx = (condition.x as u16) << 4;
+        let opcode = match (comparison, condition.rhs) {
+            ("==", Some(Operand::Register(y))) => 0x9000 | x | (y as u16) << 4,
+            ("==", Some(Operand::Value(kk))) => 0x4000 | x | kk as u16,
+            ("!=", Some(Operand::Register(y))) => 0x5000 | x | (y as u16) << 4,
+            ("!=", Some(Operand::Value(kk))) => 0x3000 | x | kk as u16,
+            ("key", _) => 0xE0A1 | x,
+            ("-key", _) => 0xE09E | x,
+            // Ordering is done by subtracting into VF, which Octo also uses as scratch
+            (comparison, rhs) => {
+                match rhs {
+                    Some(Operand::Register(y)) => self.inst(0x8F00 | (y as u16) << 4),
+                    Some(Operand::Value(kk)) => self.inst(0x6F00 | kk as u16),
+                    None => unreachable!(),
+                }
+                match comparison {
+                    // VF = rhs - Vx, the flag is set when rhs >= Vx
+                    ">" | "<=" => self.inst(0x8F05 | vx),
+                    // VF = Vx - rhs, the flag is set when Vx >= rhs
+                    _ => self.inst(0x8F07 | vx),
+                }
+                match comparison {
+                    ">" | "<" => 0x3F01,
+                    _ => 0x3F00,
+                }
+            }
+        };
+        self.inst(opcode);
+    }
+
+    fn assignment(&mut self, x: u8) -> Result<(), String> {
+        let x16 = (x as u16) << 8;
+        let op = self.next()?;
+        let opcode = match op.as_str() {
+            ":=" => match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    0xC000 | x16 | self.byte()? as u16
+                }
+                Some("key") => {
+                    self.next()?;
+                    0xF00A | x16
+                }
+                Some("delay") => {
+                    self.next()?;
+                    0xF007 | x16
+                }
+                _ => match self.operand()? {
+                    Operand::Register(y) => 0x8000 | x16 | (y as u16) << 4,
+                    Operand::Value(kk) => 0x6000 | x16 | kk as u16,
+                },
+            },
+            "+=" => match self.operand()? {
+                Operand::Register(y) => 0x8004 | x16 | (y as u16) << 4,
+                Operand::Value(kk) => 0x7000 | x16 | kk as u16,
+            },
+            "-=" => match self.operand()? {
+                Operand::Register(y) => 0x8005 | x16 | (y as u16) << 4,
+                Operand::Value(kk) => 0x7000 | x16 | kk.wrapping_neg() as u16,
+            },
+            "=-" => 0x8007 | x16 | self.register_operand()?,
+            "|=" => 0x8001 | x16 | self.register_operand()?,
+            "&=" => 0x8002 | x16 | self.register_operand()?,
+            "^=" => 0x8003 | x16 | self.register_operand()?,
+            ">>=" => 0x8006 | x16 | self.register_operand()?,
+            "<<=" => 0x800E | x16 | self.register_operand()?,
+            _ => return Err(format!("invalid operator `{}`", op)),
+        };
+        self.inst(opcode);
+        Ok(())
+    }
+
+    fn define_macro(&mut self) -> Result<(), String> {
+        let name = self.name()?;
+        let mut params = Vec::new();
+        loop {
+            match self.next()?.as_str() {
+                "{" => break,
+                param => params.push(param.to_string()),
+            }
+        }
+
+        let mut body = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = self
+                .tokens
+                .pop_front()
+                .ok_or_else(|| format!("macro `{}` is missing a `}}`", name))?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => break,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            body.push(token);
+        }
+        self.macros.insert(name, Macro { params, body });
+        Ok(())
+    }
+
+    // The body replaces the invocation in the token stream, with the arguments substituted
+    fn expand_macro(&mut self, name: &str) -> Result<(), String> {
+        let depth = self.depth + 1;
+        if depth > MAX_MACRO_DEPTH {
+            return Err(format!(
+                "macro `{}` expands more than {} levels deep, it may call itself",
+                name, MAX_MACRO_DEPTH
+            ));
+        }
+        let params = self.macros[name].params.clone();
+        let mut args = HashMap::new();
+        for param in params {
+            let arg = self.next()?;
+            args.insert(param, arg);
+        }
+        let line = self.line;
+        for token in self.macros[name].body.iter().rev() {
+            self.tokens.push_front(Token {
+                text: args.get(&token.text).unwrap_or(&token.text).clone(),
+                line,
+                depth,
+            });
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, token: &str) -> Result<(), String> {
+        match token {
+            ":" => {
+                let name = self.name()?;
+                let address = self.here();
+                if self.labels.insert(name.clone(), address).is_some() {
+                    return Err(format!("label `{}` defined twice", name));
+                }
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            }
+            ":macro" => self.define_macro()?,
+            ":byte" => {
+                let byte = self.byte()?;
+                self.rom.push(byte);
+            }
+            "return" | ";" => self.inst(0x00EE),
+            "clear" => self.inst(0x00E0),
+            "hires" => self.inst(0x00FF),
+            "lores" => self.inst(0x00FE),
+            "exit" => self.inst(0x00FD),
+            "scroll-right" => self.inst(0x00FB),
+            "scroll-left" => self.inst(0x00FC),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.inst(0x00C0 | n);
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.inst(0x00D0 | n);
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.inst(0xF033 | (x as u16) << 8);
+            }
+            "save" => {
+                let x = self.register()?;
+                self.inst(0xF055 | (x as u16) << 8);
+            }
+            "load" => {
+                let x = self.register()?;
+                self.inst(0xF065 | (x as u16) << 8);
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.inst(0xD000 | (x as u16) << 8 | (y as u16) << 4 | n);
+            }
+            "jump" => {
+                let address = self.address()?;
+                self.inst(0x1000 | address);
+            }
+            "jump0" => {
+                let address = self.address()?;
+                self.inst(0xB000 | address);
+            }
+            "call" => {
+                let address = self.address()?;
+                self.inst(0x2000 | address);
+            }
+            "native" => {
+                let address = self.address()?;
+                self.inst(address);
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let opcode = if token == "delay" { 0xF015 } else { 0xF018 };
+                self.inst(opcode | (x as u16) << 8);
+            }
+            "i" => match self.next()?.as_str() {
+                ":=" => match self.peek() {
+                    Some("hex") => {
+                        self.next()?;
+                        let x = self.register()?;
+                        self.inst(0xF029 | (x as u16) << 8);
+                    }
+                    Some("bighex") => {
+                        self.next()?;
+                        let x = self.register()?;
+                        self.inst(0xF030 | (x as u16) << 8);
+                    }
+                    _ => {
+                        let address = self.address()?;
+                        self.inst(0xA000 | address);
+                    }
+                },
+                "+=" => {
+                    let x = self.register()?;
+                    self.inst(0xF01E | (x as u16) << 8);
+                }
+                op => return Err(format!("invalid operator `{}`", op)),
+            },
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()?.as_str() {
+                    "then" => self.skip_unless(&condition, false),
+                    // Jumps to the `else` or `end` unless the condition holds
+                    "begin" => {
+                        self.skip_unless(&condition, true);
+                        self.blocks.push(Block::If {
+                            jump: self.rom.len(),
+                        });
+                        self.inst(0x1000);
+                    }
+                    token => return Err(format!("expected `then` or `begin`, found `{}`", token)),
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump }) => {
+                    let else_jump = self.rom.len();
+                    self.inst(0x1000);
+                    self.patch(jump, self.here());
+                    self.blocks.push(Block::Else { jump: else_jump });
+                }
+                _ => return Err("`else` without `if ... begin`".to_string()),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump } | Block::Else { jump }) => self.patch(jump, self.here()),
+                _ => return Err("`end` without `if ... begin`".to_string()),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.here(),
+                whiles: Vec::new(),
+            }),
+            "while" => {
+                let condition = self.condition()?;
+                self.skip_unless(&condition, true);
+                let jump = self.rom.len();
+                self.inst(0x1000);
+                match self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop { whiles, .. } => Some(whiles),
+                    _ => None,
+                }) {
+                    Some(whiles) => whiles.push(jump),
+                    None => return Err("`while` outside of a `loop`".to_string()),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, whiles }) => {
+                    self.inst(0x1000 | start);
+                    for jump in whiles {
+                        self.patch(jump, self.here());
+                    }
+                }
+                _ => return Err("`again` without `loop`".to_string()),
+            },
+            token if self.register_of(token).is_some() => {
+                let x = self.register_of(token).unwrap();
+                self.assignment(x)?;
+            }
+            token if self.macros.contains_key(token) => self.expand_macro(token)?,
+            // Bare numbers are data, usually sprites
+            token if self.value_of(token).is_some() => {
+                let byte = self.byte_of(token)?;
+                self.rom.push(byte);
+            }
+            // A bare label name calls it
+            token if is_name(token) => {
+                let address = self.address_of(token)?;
+                self.inst(0x2000 | address);
+            }
+            token => return Err(format!("unexpected `{}`", token)),
+        }
+        Ok(())
+    }
+}
+
+pub fn compile(source: &str) -> Result<Vec<u8>, OctoError> {
+    let mut compiler = Compiler {
+        tokens: tokenize(source),
+        line: 0,
+        depth: 0,
+        rom: Vec::new(),
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        macros: HashMap::new(),
+        fixups: vec![(0, 0, "main".to_string())],
+        blocks: Vec::new(),
+    };
+    compiler.inst(0x1000);
+
+    while let Ok(token) = compiler.next() {
+        compiler.statement(&token).map_err(|message| OctoError {
+            line: compiler.line,
+            message,
+        })?;
+    }
+    if !compiler.blocks.is_empty() {
+        return Err(OctoError {
+            line: compiler.line,
+            message: "missing `end` or `again`".to_string(),
+        });
+    }
+
+    for (line, offset, label) in std::mem::take(&mut compiler.fixups) {
+        match compiler.labels.get(&label) {
+            Some(&address) => compiler.patch(offset, address),
+            None if label == "main" => {
+                return Err(OctoError {
+                    line,
+                    message: "program has no `: main` label".to_string(),
+                })
+            }
+            None => {
+                return Err(OctoError {
+                    line,
+                    message: format!("undefined label `{}`", label),
+                })
+            }
+        }
+    }
+    if compiler.rom.len() > super::MAX_ROM_SIZE {
+        return Err(OctoError {
+            line: compiler.line,
+            message: format!(
+                "program is {} bytes, more than the {} that fit in memory",
+                compiler.rom.len(),
+                super::MAX_ROM_SIZE
+            ),
+        });
+    }
+    Ok(compiler.rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+
+    // Runs the program until pc reaches `end`
+    fn run_until(rom: &[u8], end: u16, setup: impl FnOnce(&mut Chip8)) -> Chip8 {
+        let mut cpu = Chip8::try_from(rom).unwrap();
+        setup(&mut cpu);
+        for _ in 0..1000 {
+            if cpu.pc == end {
+                return cpu;
+            }
+            cpu.tick().unwrap();
+        }
+        panic!("pc never reached {:03x}", end);
+    }
+
+    #[test]
+    fn hello_world() {
+        let source = "
+            : smile
+                0b00100100
+                0b00100100
+                0b00000000
+                0b10000001
+                0b01111110
+
+            : main
+                i := smile
+                v0 := 10
+                v1 := 10
+                sprite v0 v1 5
+                loop again
+        ";
+        #[rustfmt::skip]
+        let expected = [
+            0x12, 0x07,                   // jump main
+            0x24, 0x24, 0x00, 0x81, 0x7E, // smile
+            0xA2, 0x02,                   // i := smile
+            0x60, 0x0A,                   // v0 := 10
+            0x61, 0x0A,                   // v1 := 10
+            0xD0, 0x15,                   // sprite v0 v1 5
+            0x12, 0x0F,                   // loop again
+        ];
+        assert_eq!(compile(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn if_begin_else_end() {
+        let source = ": main if v0 == 1 begin v1 := 2 else v1 := 3 end";
+        #[rustfmt::skip]
+        let expected = [
+            0x12, 0x02,
+            0x30, 0x01, // Skips the jump to `else` when v0 == 1
+            0x12, 0x0A,
+            0x61, 0x02,
+            0x12, 0x0C, // Jumps over `else` to `end`
+            0x61, 0x03,
+        ];
+        let rom = compile(source).unwrap();
+        assert_eq!(rom, expected);
+
+        assert_eq!(run_until(&rom, 0x20C, |cpu| cpu.V[0] = 1).V[1], 2);
+        assert_eq!(run_until(&rom, 0x20C, |cpu| cpu.V[0] = 0).V[1], 3);
+    }
+
+    #[test]
+    fn loop_while_again() {
+        let source = ": main loop v0 += 1 while v0 != 5 again";
+        #[rustfmt::skip]
+        let expected = [
+            0x12, 0x02,
+            0x70, 0x01,
+            0x40, 0x05, // Skips the jump out of the loop while v0 != 5
+            0x12, 0x0A,
+            0x12, 0x02,
+        ];
+        let rom = compile(source).unwrap();
+        assert_eq!(rom, expected);
+        assert_eq!(run_until(&rom, 0x20A, |_| {}).V[0], 5);
+    }
+
+    #[test]
+    fn forward_labels() {
+        let source = ": main later jump done : later v0 := 1 ; : done loop again";
+        let expected = [
+            0x12, 0x02, 0x22, 0x06, 0x12, 0x0A, 0x60, 0x01, 0x00, 0xEE, 0x12, 0x0A,
+        ];
+        let rom = compile(source).unwrap();
+        assert_eq!(rom, expected);
+        assert_eq!(run_until(&rom, 0x20A, |_| {}).V[0], 1);
+
+        assert!(compile(": main jump nowhere").is_err());
+    }
+
+    #[test]
+    fn macros() {
+        let rom = compile(":macro inc2 reg { reg += 2 } : main inc2 v3 inc2 v4").unwrap();
+        assert_eq!(rom, [0x12, 0x02, 0x73, 0x02, 0x74, 0x02]);
+
+        // Recursive macros stop with an error instead of expanding forever
+        let error = compile(":macro m { m } : main m").unwrap_err();
+        assert!(error.message.contains("macro `m`"), "{}", error);
+        assert!(compile(":macro a { b } :macro b { a } : main a").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use super::{Chip8, Framebuffer};
+
+// Machine state a program can change, without the interpreter settings. Memory is boxed so
+// snapshots can be moved around without copying 4KB each time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chip8Snapshot {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub stack: [u16; 16],
+    pub sp: u16,
+    pub pc: u16,
+    pub memory: Box<[u8; 4096]>,
+    pub key_states: [bool; 16],
+    // Planes instead of a fixed size array, as the resolution can change
+    pub gfx: Framebuffer,
+    pub gfx2: Framebuffer,
+    pub plane_mask: u8,
+    pub rpl_flags: [u8; 8],
+    pub audio_buffer: [u8; 16],
+    pub audio_pitch: u8,
+}
+
+impl From<&Chip8> for Chip8Snapshot {
+    fn from(cpu: &Chip8) -> Self {
+        Self {
+            v: cpu.V,
+            i: cpu.I,
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            stack: cpu.stack,
+            sp: cpu.sp,
+            pc: cpu.pc,
+            memory: Box::new(cpu.memory),
+            key_states: cpu.key_states,
+            gfx: cpu.gfx.clone(),
+            gfx2: cpu.gfx2.clone(),
+            plane_mask: cpu.plane_mask,
+            rpl_flags: cpu.rpl_flags,
+            audio_buffer: cpu.audio_buffer,
+            audio_pitch: cpu.audio_pitch,
+        }
+    }
+}
+
+impl Chip8 {
+    // Puts the machine state back, keeping the mode, quirks, font and RNG. A halt is cleared, as
+    // the snapshot is from before it
+    pub fn restore(&mut self, snapshot: Chip8Snapshot) {
+        self.V = snapshot.v;
+        self.I = snapshot.i;
+        self.delay_timer = snapshot.delay_timer;
+        self.sound_timer = snapshot.sound_timer;
+        self.sound_active = snapshot.sound_timer > 0;
+        self.stack = snapshot.stack;
+        self.sp = snapshot.sp;
+        self.pc = snapshot.pc;
+        self.memory = *snapshot.memory;
+        self.key_states = snapshot.key_states;
+        self.gfx = snapshot.gfx;
+        self.gfx2 = snapshot.gfx2;
+        self.plane_mask = snapshot.plane_mask;
+        self.rpl_flags = snapshot.rpl_flags;
+        self.audio_buffer = snapshot.audio_buffer;
+        self.audio_pitch = snapshot.audio_pitch;
+        self.gfx_dirty = true;
+        self.halted = None;
+    }
+}
+
+// The mode, quirks, font and RNG start out as in `Chip8::new`. Use `Chip8::restore` to keep
+// those of an existing `Chip8`
+impl From<Chip8Snapshot> for Chip8 {
+    fn from(snapshot: Chip8Snapshot) -> Self {
+        let mut cpu = Chip8::new();
+        cpu.restore(snapshot);
+        cpu
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::{Mode, Quirks};
+
+    #[test]
+    fn restore_keeps_settings() {
+        let mut cpu = Chip8::with_seed(1);
+        cpu.V[3] = 7;
+        cpu.rpl_flags[0] = 1;
+        cpu.audio_pitch = 80;
+        let snapshot = Chip8Snapshot::from(&cpu);
+
+        let mut other = Chip8::new();
+        other.mode = Mode::XoChip;
+        other.quirks.vf_reset = true;
+        other.restore(snapshot.clone());
+        assert_eq!(Chip8Snapshot::from(&other), snapshot);
+        assert_eq!(other.mode, Mode::XoChip);
+        assert!(other.quirks.vf_reset);
+
+        let fresh = Chip8::from(snapshot.clone());
+        assert_eq!(Chip8Snapshot::from(&fresh), snapshot);
+        assert_eq!(fresh.mode, Mode::Chip8);
+        assert_eq!(fresh.quirks, Quirks::new());
+    }
+}
@@ -0,0 +1,98 @@
+use clap::{ArgEnum, Parser};
+
+use crate::{chip8::QuirksPreset, emu::MAX_SCALE};
+
+#[derive(Debug, Clone, Parser)]
+#[clap(version, about = "A CHIP-8 emulator and debugger")]
+pub struct Args {
+    /// ROM to load on startup. Without one the emulator starts paused, waiting for a ROM
+    #[clap(name = "ROM")]
+    pub rom_path: Option<String>,
+
+    /// Same as the positional ROM argument
+    #[clap(long = "rom", value_name = "ROM", conflicts_with = "ROM")]
+    pub rom_flag: Option<String>,
+
+    /// Download the ROM to load on startup, reusing the cached copy when offline
+    #[clap(long, value_name = "URL", conflicts_with_all = &["ROM", "rom-flag"])]
+    pub url: Option<String>,
+
+    /// Instructions executed per second
+    #[clap(long, value_name = "HZ")]
+    pub clock_rate: Option<u64>,
+
+    /// Window pixels per CHIP-8 pixel, from 1 to 32
+    #[clap(long, value_name = "N", parse(try_from_str = parse_scale))]
+    pub scale: Option<u32>,
+
+    /// Start in fullscreen. F11 or Alt+Enter switches back and forth
+    #[clap(long)]
+    pub fullscreen: bool,
+
+    /// Interpreter quirks to emulate, overriding the config file and ROM catalog
+    #[clap(long, arg_enum, value_name = "PRESET")]
+    pub quirks: Option<QuirksArg>,
+
+    /// File with an 80 byte replacement for the built-in 4x5 font
+    #[clap(long, value_name = "FILE")]
+    pub font: Option<String>,
+
+    /// Seed for RND, making random numbers the same on every run
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// Run the ROM without opening a window and print the final display
+    #[clap(long)]
+    pub headless: bool,
+
+    /// Number of instructions executed in headless mode
+    #[clap(long, default_value_t = 10_000, requires = "headless")]
+    pub cycles: u64,
+
+    /// Record the RND seed and the input of every frame to a replay file
+    #[clap(long, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<String>,
+
+    /// Play back keyboard input from a replay file instead of the keyboard
+    #[clap(long, value_name = "FILE")]
+    pub replay: Option<String>,
+
+    /// Print a listing of a ROM's instructions and exit
+    #[clap(long, value_name = "ROM")]
+    pub disassemble: Option<String>,
+
+    /// Print an instruction-level diff of two ROMs and exit
+    #[clap(long, number_of_values = 2, value_names = &["ROM_A", "ROM_B"])]
+    pub diff: Option<Vec<String>>,
+}
+
+impl Args {
+    pub fn rom(&self) -> Option<&str> {
+        self.rom_path.as_deref().or(self.rom_flag.as_deref())
+    }
+}
+
+fn parse_scale(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(scale) if (1..=MAX_SCALE).contains(&scale) => Ok(scale),
+        _ => Err(format!("expected a number from 1 to {}", MAX_SCALE)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum QuirksArg {
+    CosmacVip,
+    Chip48,
+    #[clap(name = "superchip")]
+    SuperChip,
+}
+
+impl From<QuirksArg> for QuirksPreset {
+    fn from(arg: QuirksArg) -> Self {
+        match arg {
+            QuirksArg::CosmacVip => QuirksPreset::CosmacVip,
+            QuirksArg::Chip48 => QuirksPreset::Chip48,
+            QuirksArg::SuperChip => QuirksPreset::SuperChip,
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    chip8::{Mode, Quirks},
+    emu::{
+        KeyMap, DEFAULT_BOOST_KEY, DEFAULT_CLOCK_RATE, DEFAULT_GRID_COLOR, DEFAULT_KEY_MAP,
+        DEFAULT_PALETTE, DEFAULT_SCALE, DEFAULT_STEP_KEY, DEFAULT_VOLUME,
+    },
+};
+
+// Fields missing from the file keep their default values
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub clock_rate: u64,
+    pub frame_skip: u32,
+    pub scale: u32, // Window pixels per CHIP-8 pixel
+    pub ghost_frames: u8,
+    pub show_grid: bool,
+    pub grid_color: [u8; 4],
+    pub fullscreen: bool,
+    pub auto_lower_clock_rate: bool,
+    pub mode: Mode,
+    pub palette: [[u8; 4]; 4],
+    pub key_map: KeyMap,
+    pub step_key: VirtualKeyCode,
+    pub boost_key: VirtualKeyCode,
+    pub sound_volume: f32,
+    pub sound_muted: bool,
+    pub config_changes: Vec<String>,
+    pub rom_directory: Option<PathBuf>, // Where the Open ROM dialog starts
+    // TOML needs tables after plain values
+    pub quirks: Quirks,
+    pub windows: BTreeMap<String, bool>, // Window title to whether it is open
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self {
+            clock_rate: DEFAULT_CLOCK_RATE,
+            frame_skip: 1,
+            scale: DEFAULT_SCALE,
+            ghost_frames: 0,
+            show_grid: false,
+            grid_color: DEFAULT_GRID_COLOR,
+            fullscreen: false,
+            auto_lower_clock_rate: false,
+            mode: Mode::default(),
+            palette: DEFAULT_PALETTE,
+            key_map: DEFAULT_KEY_MAP,
+            step_key: DEFAULT_STEP_KEY,
+            boost_key: DEFAULT_BOOST_KEY,
+            sound_volume: DEFAULT_VOLUME,
+            sound_muted: false,
+            config_changes: Vec::new(),
+            rom_directory: None,
+            quirks: Quirks::default(),
+            windows: BTreeMap::new(),
+        }
+    }
+
+    // ~/.config/cchipt/config.toml on Linux
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("cchipt").join("config.toml"))
+    }
+
+    // A missing file is not an error, it just means the defaults are used
+    pub fn load() -> Result<Self> {
+        let path = Self::path().ok_or_else(|| eyre!("no config directory"))?;
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| eyre!("no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
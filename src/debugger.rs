@@ -0,0 +1,315 @@
+use std::{collections::VecDeque, fmt, str::FromStr};
+
+use crate::chip8::{Chip8, CpuError, MemoryAccess};
+
+pub const DEFAULT_TRACE_DEPTH: usize = 256;
+
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub mnemonic: String,
+    pub registers: [u8; 16], // V0 through VF before the instruction ran
+}
+
+impl TraceEntry {
+    // Taken before the instruction at pc runs
+    pub fn new(cpu: &Chip8) -> Self {
+        let opcode = cpu.get_opcode();
+        Self {
+            pc: cpu.pc,
+            opcode,
+            mnemonic: Chip8::decode_instruction(&opcode),
+            registers: cpu.V,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    pub const ALL: [WatchKind; 3] = [WatchKind::Read, WatchKind::Write, WatchKind::ReadWrite];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WatchKind::Read => "Read",
+            WatchKind::Write => "Write",
+            WatchKind::ReadWrite => "Read/Write",
+        }
+    }
+
+    pub fn matches(&self, address: u16, access: &MemoryAccess) -> bool {
+        match (self, access) {
+            (WatchKind::Read | WatchKind::ReadWrite, MemoryAccess::Read(range))
+            | (WatchKind::Write | WatchKind::ReadWrite, MemoryAccess::Write(range)) => {
+                range.contains(&address)
+            }
+            _ => false,
+        }
+    }
+}
+
+// Pauses when the condition becomes true, written like `V3 == 42` or `I > 300` with hex values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionalBreakpoint {
+    RegisterEquals(u8, u8),
+    RegisterGreater(u8, u8),
+    RegisterLess(u8, u8),
+    IEquals(u16),
+    IGreater(u16),
+    ILess(u16),
+}
+
+impl ConditionalBreakpoint {
+    pub fn matches(&self, cpu: &Chip8) -> bool {
+        match *self {
+            ConditionalBreakpoint::RegisterEquals(x, value) => cpu.V[x as usize] == value,
+            ConditionalBreakpoint::RegisterGreater(x, value) => cpu.V[x as usize] > value,
+            ConditionalBreakpoint::RegisterLess(x, value) => cpu.V[x as usize] < value,
+            ConditionalBreakpoint::IEquals(value) => cpu.I == value,
+            ConditionalBreakpoint::IGreater(value) => cpu.I > value,
+            ConditionalBreakpoint::ILess(value) => cpu.I < value,
+        }
+    }
+}
+
+impl FromStr for ConditionalBreakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split_whitespace().collect::<Vec<_>>();
+        let (target, op, value) = match parts[..] {
+            [target, op, value] => (target, op, value),
+            _ => {
+                return Err(String::from(
+                    "expected <register> <op> <value>, e.g. V3 == 42",
+                ))
+            }
+        };
+        let value = u16::from_str_radix(value, 16)
+            .map_err(|_| format!("invalid hex value \"{}\"", value))?;
+
+        let target = target.to_ascii_uppercase();
+        if target == "I" {
+            return match op {
+                "==" => Ok(ConditionalBreakpoint::IEquals(value)),
+                ">" => Ok(ConditionalBreakpoint::IGreater(value)),
+                "<" => Ok(ConditionalBreakpoint::ILess(value)),
+                _ => Err(format!("unknown comparison \"{}\"", op)),
+            };
+        }
+
+        let x = target
+            .strip_prefix('V')
+            .filter(|x| x.len() == 1)
+            .and_then(|x| u8::from_str_radix(x, 16).ok())
+            .ok_or_else(|| format!("unknown register \"{}\"", target))?;
+        let value =
+            u8::try_from(value).map_err(|_| format!("{:x} does not fit in V{:X}", value, x))?;
+        match op {
+            "==" => Ok(ConditionalBreakpoint::RegisterEquals(x, value)),
+            ">" => Ok(ConditionalBreakpoint::RegisterGreater(x, value)),
+            "<" => Ok(ConditionalBreakpoint::RegisterLess(x, value)),
+            _ => Err(format!("unknown comparison \"{}\"", op)),
+        }
+    }
+}
+
+impl fmt::Display for ConditionalBreakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConditionalBreakpoint::RegisterEquals(x, value) => {
+                write!(f, "V{:X} == {:02x}", x, value)
+            }
+            ConditionalBreakpoint::RegisterGreater(x, value) => {
+                write!(f, "V{:X} > {:02x}", x, value)
+            }
+            ConditionalBreakpoint::RegisterLess(x, value) => write!(f, "V{:X} < {:02x}", x, value),
+            ConditionalBreakpoint::IEquals(value) => write!(f, "I == {:03x}", value),
+            ConditionalBreakpoint::IGreater(value) => write!(f, "I > {:03x}", value),
+            ConditionalBreakpoint::ILess(value) => write!(f, "I < {:03x}", value),
+        }
+    }
+}
+
+// Drops the oldest entries so the log stays within `depth`
+pub fn record_trace(log: &mut VecDeque<TraceEntry>, depth: usize, cpu: &Chip8) {
+    while log.len() >= depth.max(1) {
+        log.pop_front();
+    }
+    log.push_back(TraceEntry::new(cpu));
+}
+
+// Address of the first watchpoint the access touches
+pub fn find_watchpoint(
+    watchpoints: &[(u16, WatchKind)],
+    access: Option<&MemoryAccess>,
+) -> Option<u16> {
+    let access = access?;
+    watchpoints
+        .iter()
+        .find(|(address, kind)| kind.matches(*address, access))
+        .map(|(address, _)| *address)
+}
+
+// What one instruction ran into, found by `debug_tick` for both `Debugger` and `Emu`
+pub struct TickOutcome {
+    pub result: Result<(), CpuError>,
+    pub access: Option<MemoryAccess>, // Memory the instruction read or wrote
+    pub watchpoint: Option<u16>,
+    pub conditions_met: Vec<ConditionalBreakpoint>, // Only those that were false before
+    pub breakpoint: Option<u16>,                    // pc after the tick, if it has a breakpoint
+}
+
+// Runs one instruction and checks it against the breakpoints, watchpoints and conditions.
+// Conditions only count when they become true, so running again carries on while they hold
+pub fn debug_tick(
+    cpu: &mut Chip8,
+    breakpoints: &[u16],
+    watchpoints: &[(u16, WatchKind)],
+    conditions: &[ConditionalBreakpoint],
+) -> TickOutcome {
+    // Found before the tick, which changes the registers the access depends on
+    let access = cpu.memory_access();
+    let watchpoint = find_watchpoint(watchpoints, access.as_ref());
+    let already_met = conditions
+        .iter()
+        .map(|condition| condition.matches(cpu))
+        .collect::<Vec<_>>();
+
+    let result = cpu.tick();
+
+    let conditions_met = conditions
+        .iter()
+        .zip(already_met)
+        .filter(|(condition, already_met)| !already_met && condition.matches(cpu))
+        .map(|(condition, _)| *condition)
+        .collect();
+    // Checked after the tick, so running again continues past the breakpoint
+    let breakpoint = Some(cpu.pc).filter(|pc| breakpoints.contains(pc));
+    TickOutcome {
+        result,
+        access,
+        watchpoint,
+        conditions_met,
+        breakpoint,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugEvent {
+    Stepped,
+    BreakpointHit(u16),     // pc the breakpoint is on
+    WatchpointHit(u16, u8), // Watched address and its value after the access
+    ConditionMet(ConditionalBreakpoint),
+    Halted(String), // Why the CPU stopped
+}
+
+// Breakpoints, watchpoints, conditions and tracing around a bare `Chip8`, without the GUI or
+// timing that `Emu` adds, so a ROM can be debugged from a test or script. Timers are left to the
+// caller
+pub struct Debugger {
+    pub cpu: Chip8,
+    pub breakpoints: Vec<u16>,
+    pub watchpoints: Vec<(u16, WatchKind)>,
+    pub conditions: Vec<ConditionalBreakpoint>,
+    pub trace_enabled: bool,
+    pub trace_log: VecDeque<TraceEntry>, // Newest last
+    pub trace_depth: usize,
+}
+
+impl Debugger {
+    pub fn new(cpu: Chip8) -> Self {
+        Self {
+            cpu,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            conditions: Vec::new(),
+            trace_enabled: false,
+            trace_log: VecDeque::new(),
+            trace_depth: DEFAULT_TRACE_DEPTH,
+        }
+    }
+
+    // Runs one instruction. Like `Emu::progress`, a breakpoint is reported once pc reaches
+    // it, so stepping again carries on past it
+    pub fn step(&mut self) -> DebugEvent {
+        if let Some(error) = &self.cpu.halted {
+            return DebugEvent::Halted(error.to_string());
+        }
+        if self.trace_enabled {
+            record_trace(&mut self.trace_log, self.trace_depth, &self.cpu);
+        }
+
+        let outcome = debug_tick(
+            &mut self.cpu,
+            &self.breakpoints,
+            &self.watchpoints,
+            &self.conditions,
+        );
+        if let Err(error) = outcome.result {
+            return DebugEvent::Halted(error.to_string());
+        }
+
+        if let Some(address) = outcome.watchpoint {
+            DebugEvent::WatchpointHit(address, self.cpu.memory[address as usize])
+        } else if let Some(&condition) = outcome.conditions_met.first() {
+            DebugEvent::ConditionMet(condition)
+        } else if let Some(address) = outcome.breakpoint {
+            DebugEvent::BreakpointHit(address)
+        } else {
+            DebugEvent::Stepped
+        }
+    }
+
+    // Steps until something other than `Stepped` happens, giving up after `max_steps`
+    pub fn run(&mut self, max_steps: u64) -> DebugEvent {
+        for _ in 0..max_steps {
+            match self.step() {
+                DebugEvent::Stepped => {}
+                event => return event,
+            }
+        }
+        DebugEvent::Stepped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::CpuError;
+
+    #[test]
+    fn debugger_events() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x07, // LD   V0, 07
+            0xA3, 0x00, // LD   I, 300
+            0xF0, 0x55, // LD   [I], V0
+            0x71, 0x01, // ADD  V1, 01
+            0x00, 0xEE, // RET with nothing to return to
+        ];
+        let mut debugger = Debugger::new(Chip8::try_from(&rom[..]).unwrap());
+        debugger.trace_enabled = true;
+        debugger.breakpoints.push(0x204);
+        debugger.watchpoints.push((0x300, WatchKind::Write));
+        debugger.conditions.push("V1 == 1".parse().unwrap());
+
+        assert_eq!(debugger.step(), DebugEvent::Stepped);
+        assert_eq!(debugger.run(10), DebugEvent::BreakpointHit(0x204));
+        assert_eq!(debugger.step(), DebugEvent::WatchpointHit(0x300, 7));
+        assert_eq!(
+            debugger.step(),
+            DebugEvent::ConditionMet(ConditionalBreakpoint::RegisterEquals(1, 1))
+        );
+        let halted = DebugEvent::Halted(CpuError::StackUnderflow(0x208).to_string());
+        assert_eq!(debugger.run(10), halted);
+        assert_eq!(debugger.step(), halted);
+        assert_eq!(debugger.trace_log.len(), 5);
+        assert_eq!(debugger.trace_log[0].pc, 0x200);
+    }
+}
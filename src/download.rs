@@ -0,0 +1,183 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use color_eyre::{eyre::eyre, Report, Result};
+use reqwest::header::CONTENT_TYPE;
+use sha2::{Digest, Sha256};
+
+use crate::chip8::MAX_ROM_SIZE;
+
+pub struct DownloadedRom {
+    pub name: Option<String>, // File name without the extension, like `Emu::rom_name`
+    pub bytes: Vec<u8>,
+}
+
+pub enum DownloadEvent {
+    Progress { received: usize, total: Option<u64> },
+    Done(Result<DownloadedRom>),
+}
+
+// Last path segment of the URL, without the query or fragment
+pub fn file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    match path
+        .split_once('/')
+        .and_then(|(_, path)| path.rsplit('/').next())
+    {
+        Some(name) if !matches!(name, "" | "." | "..") => name.to_string(),
+        _ => "rom.ch8".to_string(),
+    }
+}
+
+// ~/.cache/cchipt/roms/<SHA-256 of the URL> on Linux, so ROMs with the same file name on
+// different sites do not replace each other
+pub fn cache_path(url: &str) -> Option<PathBuf> {
+    let key = format!("{:x}", Sha256::digest(url));
+    dirs::cache_dir().map(|dir| dir.join("cchipt").join("roms").join(key))
+}
+
+// An HTML error page or other text served in place of the ROM is rejected, anything else is
+// taken to be the ROM as servers rarely know a CHIP-8 content type
+fn is_binary(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    !(media_type.starts_with("text/")
+        || matches!(
+            media_type,
+            "application/json" | "application/xml" | "application/xhtml+xml"
+        ))
+}
+
+// Blocks until the whole body is read, calling `progress` after every chunk
+pub fn fetch(url: &str, mut progress: impl FnMut(usize, Option<u64>)) -> Result<Vec<u8>> {
+    let mut response = reqwest::blocking::get(url)?.error_for_status()?;
+    if let Some(content_type) = response.headers().get(CONTENT_TYPE) {
+        let content_type = content_type.to_str().unwrap_or_default();
+        if !is_binary(content_type) {
+            return Err(eyre!("expected a ROM, got a {} response", content_type));
+        }
+    }
+
+    let total = response.content_length();
+    if matches!(total, Some(total) if total > MAX_ROM_SIZE as u64) {
+        return Err(eyre!(
+            "ROM is {} bytes, more than the {} that fit in memory",
+            total.unwrap(),
+            MAX_ROM_SIZE
+        ));
+    }
+
+    // The length header can be missing or wrong, so the size is checked while reading too
+    let mut bytes = Vec::new();
+    let mut chunk = [0; 512];
+    loop {
+        let read = response.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+        if bytes.len() > MAX_ROM_SIZE {
+            return Err(eyre!(
+                "ROM is more than the {} bytes that fit in memory",
+                MAX_ROM_SIZE
+            ));
+        }
+        progress(bytes.len(), total);
+    }
+    Ok(bytes)
+}
+
+// Falls back to the copy cached by an earlier download when the ROM cannot be fetched, so a
+// ROM keeps working offline
+pub fn fetch_rom(url: &str, progress: impl FnMut(usize, Option<u64>)) -> Result<DownloadedRom> {
+    let cache_path = cache_path(url);
+    let bytes = match fetch(url, progress) {
+        Ok(bytes) => {
+            if let Some(path) = &cache_path {
+                if let Err(e) = write_cache(path, &bytes) {
+                    eprintln!("Failed to cache {}: {}", path.display(), e);
+                }
+            }
+            bytes
+        }
+        // A response that was refused for its size or content type is not a reason to use an
+        // older copy
+        Err(e) if !is_unreachable(&e) => return Err(e),
+        Err(e) => match cache_path.as_ref().filter(|path| path.exists()) {
+            Some(path) => {
+                eprintln!("Failed to download {}, using the cached copy: {}", url, e);
+                fs::read(path)?
+            }
+            None => return Err(e),
+        },
+    };
+
+    let name = Path::new(&file_name(url))
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned());
+    Ok(DownloadedRom { name, bytes })
+}
+
+// The server could not be reached, the connection dropped or it answered with an error status
+fn is_unreachable(error: &Report) -> bool {
+    error.downcast_ref::<reqwest::Error>().is_some()
+        || error.downcast_ref::<std::io::Error>().is_some()
+}
+
+fn write_cache(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+// Downloads on another thread so the window stays responsive, `Done` is always the last event
+pub fn spawn(url: String) -> Receiver<DownloadEvent> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let progress_sender = sender.clone();
+        let result = fetch_rom(&url, |received, total| {
+            let _ = progress_sender.send(DownloadEvent::Progress { received, total });
+        });
+        let _ = sender.send(DownloadEvent::Done(result));
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_is_keyed_by_the_whole_url() {
+        let a = cache_path("https://example.com/a/pong.ch8");
+        let b = cache_path("https://example.org/b/pong.ch8");
+        if let (Some(a), Some(b)) = (a, b) {
+            assert_ne!(a, b);
+            assert!(a.ends_with(format!(
+                "roms/{:x}",
+                Sha256::digest("https://example.com/a/pong.ch8")
+            )));
+        }
+    }
+
+    #[test]
+    fn only_unreachable_servers_fall_back_to_the_cache() {
+        // Nothing listens on port 1
+        let refused = fetch("http://127.0.0.1:1/pong.ch8", |_, _| ()).unwrap_err();
+        assert!(is_unreachable(&refused));
+        assert!(is_unreachable(
+            &std::io::Error::other("connection reset").into()
+        ));
+
+        assert!(!is_unreachable(&eyre!(
+            "expected a ROM, got a text/html response"
+        )));
+    }
+}
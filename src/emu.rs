@@ -1,37 +1,76 @@
-use color_eyre::Result;
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    ops::Range,
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
 use winit::event::VirtualKeyCode;
 
-use crate::chip8::Chip8;
+use crate::{
+    audio::{AudioPattern, Sound},
+    catalog::RomInfo,
+    chip8::{
+        octo, Chip8, Chip8Snapshot, CpuError, Framebuffer, MemoryAccess, Mode, Quirks,
+        QuirksPreset, RomError,
+    },
+    cli::Args,
+    config::Config,
+    debugger::{debug_tick, record_trace},
+    state::EmuState,
+};
 
-pub const SCREEN_WIDTH: u32 = 64;
-pub const SCREEN_HEIGHT: u32 = 32;
+pub use crate::{
+    chip8::{CHARACTER_SPRITES, LARGE_CHARACTER_SPRITES, SCREEN_HEIGHT, SCREEN_WIDTH},
+    debugger::{ConditionalBreakpoint, TraceEntry, WatchKind, DEFAULT_TRACE_DEPTH},
+};
 
-pub const SCALE: u32 = 16;
+// Window pixels per CHIP-8 pixel
+pub const DEFAULT_SCALE: u32 = 16;
+pub const MAX_SCALE: u32 = 32;
 pub const REFRESH_RATE: u64 = 60;
+pub const DEFAULT_CLOCK_RATE: u64 = 600;
+
+// RGBA colours indexed by the XO-CHIP bitplanes a pixel is set in. Plain CHIP-8 only uses the first two
+pub const DEFAULT_PALETTE: [[u8; 4]; 4] = [
+    [0x11, 0x11, 0x11, 0xff], // Off
+    [0xff, 0xff, 0xff, 0xff], // First plane
+    [0x77, 0x77, 0x77, 0xff], // Second plane
+    [0xbb, 0xbb, 0xbb, 0xff], // Both planes
+];
+
+pub const DEFAULT_GRID_COLOR: [u8; 4] = [0x33, 0x33, 0x33, 0xff];
+
+// Cells smaller than this, in window pixels, would be mostly grid, so no grid is drawn
+const MIN_GRID_CELL: usize = 3;
 
-pub const WINDOW_HEIGHT: u32 = SCREEN_HEIGHT * SCALE;
-pub const WINDOW_WIDTH: u32 = SCREEN_WIDTH * SCALE;
-
-pub const CHARACTER_SPRITES: [u8; 0x50] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x08, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x08, 0x80, // F
+// Narrower 3x5 digits, as an alternative to the standard font
+pub const THIN_CHARACTER_SPRITES: [u8; 0x50] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0xC0, 0x40, 0x40, 0xE0, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0x60, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x40, 0x40, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0x40, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xC0, 0xA0, 0xC0, // B
+    0x60, 0x80, 0x80, 0x80, 0x60, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xC0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
 ];
 
-pub const KEYS: [VirtualKeyCode; 16] = [
+// Host keys for CHIP-8 keys 0 through F
+pub type KeyMap = [VirtualKeyCode; 16];
+
+pub const DEFAULT_KEY_MAP: KeyMap = [
     VirtualKeyCode::Key0,
     VirtualKeyCode::Key1,
     VirtualKeyCode::Key2,
@@ -50,59 +89,1365 @@ pub const KEYS: [VirtualKeyCode; 16] = [
     VirtualKeyCode::F,
 ];
 
+// Executes a single instruction while paused
+pub const DEFAULT_STEP_KEY: VirtualKeyCode = VirtualKeyCode::S;
+
+// Runs the emulator faster while held
+pub const DEFAULT_BOOST_KEY: VirtualKeyCode = VirtualKeyCode::Tab;
+pub const BOOST_FACTOR: u64 = 10;
+
+pub const DEFAULT_VOLUME: f32 = 1.0;
+
+// The COSMAC VIP hex keypad laid out on the left side of a QWERTY keyboard:
+//   1 2 3 C     1 2 3 4
+//   4 5 6 D  →  Q W E R
+//   7 8 9 E     A S D F
+//   A 0 B F     Z X C V
+pub const HEX_PAD_KEY_MAP: KeyMap = [
+    VirtualKeyCode::X,
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Q,
+    VirtualKeyCode::W,
+    VirtualKeyCode::E,
+    VirtualKeyCode::A,
+    VirtualKeyCode::S,
+    VirtualKeyCode::D,
+    VirtualKeyCode::Z,
+    VirtualKeyCode::C,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::R,
+    VirtualKeyCode::F,
+    VirtualKeyCode::V,
+];
+
+pub const BENCHMARK_TICKS: u32 = 10_000;
+
+// Draws, adds and picks random numbers in a loop, a typical mix of game instructions
+const BENCHMARK_ROM: [u8; 12] = [
+    0x60, 0x00, // LD   V0, 00
+    0xA0, 0x00, // LD   I, 000
+    0x70, 0x01, // ADD  V0, 01
+    0xD0, 0x15, // DRW  V0, V1, 5
+    0xC2, 0xFF, // RND  V2, ff
+    0x12, 0x04, // JP   204
+];
+
+pub const CONFIG_CHANGE_LOG_LEN: usize = 100;
+
+pub type ConfigChangeLog = Vec<(Instant, String)>;
+
+pub const FRAME_TIMING_LOG_LEN: usize = 3600;
+
+pub const CONSOLE_LOG_LEN: usize = 500;
+
+// Only the newest events are kept, a ROM that keeps tripping a watchpoint would otherwise
+// grow the list without bound
+pub const EVENT_LOG_LEN: usize = 500;
+
+// Messages for the Console window, like halts and ROMs that failed to load. Oldest first, only
+// the newest `CONSOLE_LOG_LEN` are kept
+#[derive(Debug, Default)]
+pub struct ConsoleLog {
+    pub lines: VecDeque<String>,
+}
+
+impl ConsoleLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, message: String) {
+        if self.lines.len() >= CONSOLE_LOG_LEN {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(message);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+// (target_ms, actual_ms, delta_ms) for each frame
+pub type FrameTimingLog = VecDeque<(u64, u64, u64)>;
+
+// Frames the FPS overlay averages over
+pub const FPS_SAMPLES: usize = 60;
+const FPS_DIGIT_SCALE: usize = 4; // Window pixels per font pixel
+
+// Longest ghost trail, in rendered frames
+pub const MAX_GHOST_FRAMES: u8 = 15;
+
+// Enough to step back through a few frames at the default clock rate
+pub const HISTORY_LEN: usize = 1000;
+
+// pc values kept for the CPU State sparkline
+pub const PC_HISTORY_LEN: usize = 64;
+
+// State after a headless run, for checking a ROM's output without a window
+pub struct HeadlessResult {
+    pub gfx: Framebuffer,
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub cycles: u64, // Instructions executed, less than asked for if it stopped early
+    pub halted: Option<CpuError>, // The run stops when the CPU halts, e.g. at an unknown opcode
+}
+
+// Spreads the clock rate over display frames. Whatever does not divide evenly is carried over
+// to the next frame, so e.g. 700 Hz runs 11 or 12 instructions a frame instead of always 11
+pub struct FrameTimer {
+    remainder: u64, // In 1/REFRESH_RATE instructions
+}
+
+impl FrameTimer {
+    pub fn new() -> Self {
+        Self { remainder: 0 }
+    }
+
+    // Instructions to run this frame
+    pub fn steps(&mut self, clock_rate: u64) -> u64 {
+        let total = self.remainder + clock_rate;
+        self.remainder = total % REFRESH_RATE;
+        total / REFRESH_RATE
+    }
+}
+
+impl Default for FrameTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct SpeedChallenge {
+    pub stage: u64,
+    pub total_stages: u64,
+    step_hz: u64,
+    max_hz: u64,
+    interval: Duration,
+    last_step: Instant,
+}
+
+pub enum EmuEvent {
+    RomModified {
+        address: u16,
+    },
+    BreakpointHit {
+        address: u16,
+    },
+    WatchpointHit {
+        address: u16,
+        pc: u16,
+        opcode: u16,
+    },
+    ConditionMet {
+        condition: ConditionalBreakpoint,
+        pc: u16,
+    },
+    Halted {
+        error: CpuError,
+    },
+    SpeedChallengePaused {
+        clock_rate: u64,
+    },
+}
+
 pub struct Emu {
     pub cpu: Chip8,
+    pub sound: Sound,
+    pub sound_volume: f32, // From 0 to 1
+    pub sound_muted: bool,
+    pub palette: [[u8; 4]; 4],
+    pub key_map: KeyMap,
+    pub step_key: VirtualKeyCode,
+    pub boost_key: VirtualKeyCode,
+    pub boosted: bool, // Set by the event loop while the boost key is held
     pub run_steps: bool,
+    pub step_size: u32, // Instructions run by one Step while paused
     pub clock_rate: u64,
+    pub frame_skip: u32,
+    pub scale: u32, // Only read when the window is created
+    pub max_clock_rate: u64,
+    pub auto_lower_clock_rate: bool,
+    capacity_checked_rate: u64,
+    pub speed_challenge: Option<SpeedChallenge>,
+    pub rom: Vec<u8>,
+    pub rom_name: Option<String>, // File name without the extension, if loaded from a file
+    pub rom_info: Option<RomInfo>,
+    pub rom_warning: Option<RomError>, // First problem Chip8::validate_rom found in the ROM
+    pub initial_rom_checksum: u32,
+    pub detect_self_modification: bool,
+    pub events: VecDeque<EmuEvent>, // Oldest first
+    events_pushed: u64,
+    pub breakpoints: Vec<u16>,
+    pub temporary_breakpoints: Vec<u16>, // Removed when hit, used by run to cursor
+    pub memory_watchpoints: Vec<(u16, WatchKind)>,
+    pub conditional_breakpoints: Vec<ConditionalBreakpoint>,
+    pub history: VecDeque<Chip8Snapshot>, // CPU state before each of the last steps, newest last
+    pub pc_history: VecDeque<u16>,        // pc of the last instructions executed, newest last
+    pub trace_enabled: bool,
+    pub trace_log: VecDeque<TraceEntry>, // Newest last
+    pub trace_depth: usize,
+    pub total_cycles: u64,
+    timer_remainder: u64, // In 1/clock_rate timer ticks
+    pub last_memory_write: Option<(Range<u16>, u64)>, // Addresses and total_cycles after the write
+    pub total_frames: u64,
+    pub effective_clock_rate: u64, // Instructions actually executed in the last second
+    pub config_changes: ConfigChangeLog,
+    pub console: ConsoleLog,
+    pub frame_timings: FrameTimingLog,
+    pub show_fps: bool,
+    pub show_grid: bool,
+    pub grid_color: [u8; 4],
+    pub fullscreen: bool, // Kept up to date by the event loop, which owns the window
+    pub presentation_mode: bool, // Only the display is shown, scaled up to fill the window
+    pub presentation_scale: u32, // Set by the event loop from the window size
+    pub ghost_frames: u8, // Frames a pixel takes to fade out after turning off, 0 for none
+    gfx_history: VecDeque<Vec<usize>>, // Palette index of every pixel in the last frames, newest last
+    frame_durations: VecDeque<Duration>, // Time between the last rendered frames, newest last
+    last_frame_at: Option<Instant>,
+    last_rom_checksum: u32,
+    clock_rate_sample: (Instant, u64), // Start of the current second and total_cycles at that time
 }
 
 impl Default for Emu {
     fn default() -> Self {
+        Self::with_sound(Sound::new())
+    }
+}
+
+impl Emu {
+    // `Sound::silent` keeps emulators nobody listens to off the audio device
+    pub fn with_sound(sound: Sound) -> Self {
         Self {
             cpu: Default::default(),
+            sound,
+            sound_volume: DEFAULT_VOLUME,
+            sound_muted: false,
+            palette: DEFAULT_PALETTE,
+            key_map: DEFAULT_KEY_MAP,
+            step_key: DEFAULT_STEP_KEY,
+            boost_key: DEFAULT_BOOST_KEY,
+            boosted: false,
             run_steps: true,
-            clock_rate: 600,
+            step_size: 1,
+            clock_rate: DEFAULT_CLOCK_RATE,
+            frame_skip: 1,
+            scale: DEFAULT_SCALE,
+            max_clock_rate: u64::MAX,
+            auto_lower_clock_rate: false,
+            capacity_checked_rate: 0,
+            speed_challenge: None,
+            rom: Vec::new(),
+            rom_name: None,
+            rom_info: None,
+            rom_warning: None,
+            initial_rom_checksum: 0,
+            detect_self_modification: false,
+            events: VecDeque::with_capacity(EVENT_LOG_LEN),
+            events_pushed: 0,
+            breakpoints: Vec::new(),
+            temporary_breakpoints: Vec::new(),
+            memory_watchpoints: Vec::new(),
+            conditional_breakpoints: Vec::new(),
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            pc_history: VecDeque::with_capacity(PC_HISTORY_LEN),
+            trace_enabled: false,
+            trace_log: VecDeque::with_capacity(DEFAULT_TRACE_DEPTH),
+            trace_depth: DEFAULT_TRACE_DEPTH,
+            total_cycles: 0,
+            timer_remainder: 0,
+            last_memory_write: None,
+            total_frames: 0,
+            effective_clock_rate: 0,
+            config_changes: Vec::new(),
+            console: ConsoleLog::new(),
+            frame_timings: VecDeque::with_capacity(FRAME_TIMING_LOG_LEN),
+            show_fps: false,
+            show_grid: false,
+            grid_color: DEFAULT_GRID_COLOR,
+            fullscreen: false,
+            presentation_mode: false,
+            presentation_scale: DEFAULT_SCALE,
+            ghost_frames: 0,
+            gfx_history: VecDeque::with_capacity(MAX_GHOST_FRAMES as usize),
+            frame_durations: VecDeque::with_capacity(FPS_SAMPLES),
+            last_frame_at: None,
+            last_rom_checksum: 0,
+            clock_rate_sample: (Instant::now(), 0),
         }
     }
-}
 
-impl Emu {
-    pub fn update_keystates(&mut self, new_keystates: [bool; 16]) {
-        self.cpu.key_states = new_keystates;
+    pub fn update_keystates(&mut self, held: [bool; 16], pressed: [bool; 16]) {
+        self.cpu.key_states = held;
+        self.cpu.key_presses = pressed;
+    }
+
+    // One display frame of the event loop: as many instructions as are due at the clock rate,
+    // stopping early at a breakpoint or halt. Returns the error if the CPU halted
+    pub fn run_frame(&mut self, frame_timer: &mut FrameTimer) -> Result<(), CpuError> {
+        self.update_speed_challenge();
+        // No minimum of one step: frames only run on their deadline now, so a frame never
+        // sleeps through its interval, and below 60 Hz some frames rightly run nothing
+        let mut result = Ok(());
+        for _ in 0..frame_timer.steps(self.frame_clock_rate()) {
+            result = self.progress();
+            // Stop at a breakpoint or halt instead of finishing the frame
+            if self.run_steps {
+                break;
+            }
+        }
+        self.check_self_modification();
+        result
+    }
+
+    // Returns the error the CPU halted with, if this step halted it
+    pub fn progress(&mut self) -> Result<(), CpuError> {
+        // A jump to itself is the usual way for a ROM to spin forever
+        let opcode = self.cpu.get_opcode();
+        if opcode & 0xF000 == 0x1000 && opcode & 0x0FFF == self.cpu.pc {
+            self.speed_challenge = None;
+        }
+
+        if self.history.len() >= HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(Chip8Snapshot::from(&self.cpu));
+        if self.pc_history.len() >= PC_HISTORY_LEN {
+            self.pc_history.pop_front();
+        }
+        self.pc_history.push_back(self.cpu.pc);
+
+        if self.trace_enabled {
+            record_trace(&mut self.trace_log, self.trace_depth, &self.cpu);
+        }
+
+        let pc = self.cpu.pc;
+        let outcome = debug_tick(
+            &mut self.cpu,
+            &self.breakpoints,
+            &self.memory_watchpoints,
+            &self.conditional_breakpoints,
+        );
+        let result = outcome.result;
+        if let Some(address) = outcome.watchpoint {
+            self.run_steps = true;
+            self.push_event(EmuEvent::WatchpointHit {
+                address,
+                pc,
+                opcode,
+            });
+        }
+
+        self.total_cycles += 1;
+        if self.cpu.halted.is_none() {
+            self.update_timers();
+        }
+        if let Err(error) = result {
+            self.run_steps = true;
+            self.console.push(format!(
+                "CPU halted after {} instructions: {}",
+                self.total_cycles, error
+            ));
+            if self.speed_challenge.take().is_some() {
+                self.console
+                    .push(format!("Speed challenge stopped at {} Hz", self.clock_rate));
+            }
+            self.push_event(EmuEvent::Halted { error });
+        }
+        if let Some(MemoryAccess::Write(range)) = outcome.access {
+            self.last_memory_write = Some((range, self.total_cycles));
+        }
+        self.update_sound();
+
+        for condition in outcome.conditions_met {
+            self.run_steps = true;
+            self.push_event(EmuEvent::ConditionMet {
+                condition,
+                pc: self.cpu.pc,
+            });
+        }
+        if let Some(address) = outcome.breakpoint {
+            self.run_steps = true;
+            self.push_event(EmuEvent::BreakpointHit { address });
+        }
+        if let Some(i) = self
+            .temporary_breakpoints
+            .iter()
+            .position(|a| *a == self.cpu.pc)
+        {
+            self.temporary_breakpoints.remove(i);
+            self.run_steps = true;
+        }
+        result
+    }
+
+    // Runs without waiting for the display, apart from giving DRW a vblank every frame's worth of
+    // instructions
+    pub fn run_headless(&mut self, cycles: u64) -> HeadlessResult {
+        let cycles_per_frame = (self.clock_rate / REFRESH_RATE).max(1);
+        let mut executed = 0;
+        while executed < cycles && self.cpu.halted.is_none() {
+            if executed % cycles_per_frame == 0 {
+                self.cpu.vblank = true;
+            }
+            let _ = self.progress();
+            executed += 1;
+        }
+
+        HeadlessResult {
+            gfx: self.cpu.gfx.clone(),
+            registers: self.cpu.V,
+            i: self.cpu.I,
+            pc: self.cpu.pc,
+            cycles: executed,
+            halted: self.cpu.halted,
+        }
+    }
+
+    pub fn update_effective_clock_rate(&mut self) {
+        let (start, cycles) = self.clock_rate_sample;
+        let elapsed = start.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            let executed = self.total_cycles - cycles;
+            self.effective_clock_rate = (executed as f64 / elapsed.as_secs_f64()).round() as u64;
+            self.clock_rate_sample = (Instant::now(), self.total_cycles);
+        }
+    }
+
+    // Counted in whole numbers, so the timers stay exact at any clock rate
+    fn update_timers(&mut self) {
+        let clock_rate = self.clock_rate.max(1);
+        self.timer_remainder += REFRESH_RATE;
+        while self.timer_remainder >= clock_rate {
+            self.timer_remainder -= clock_rate;
+            self.cpu.tick_timers();
+        }
+    }
+
+    // What the sound output is set to, taking muting into account
+    pub fn output_volume(&self) -> f32 {
+        if self.sound_muted {
+            0.0
+        } else {
+            self.sound_volume
+        }
+    }
+
+    // XO-CHIP ROMs play their own audio pattern instead of the tone
+    fn update_sound(&mut self) {
+        let pattern = (self.cpu.mode == Mode::XoChip).then_some(AudioPattern {
+            buffer: self.cpu.audio_buffer,
+            pitch: self.cpu.audio_pitch,
+        });
+        self.sound.set_pattern(pattern);
+        self.sound.set_active(self.cpu.sound_active);
+    }
+
+    fn push_event(&mut self, event: EmuEvent) {
+        // The challenge holds its clock rate until the program runs again
+        let pauses = matches!(
+            event,
+            EmuEvent::BreakpointHit { .. }
+                | EmuEvent::WatchpointHit { .. }
+                | EmuEvent::ConditionMet { .. }
+        );
+        self.record_event(event);
+        if pauses && self.speed_challenge.is_some() {
+            self.record_event(EmuEvent::SpeedChallengePaused {
+                clock_rate: self.clock_rate,
+            });
+        }
+    }
+
+    fn record_event(&mut self, event: EmuEvent) {
+        if self.events.len() >= EVENT_LOG_LEN {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+        self.events_pushed += 1;
+    }
+
+    // Carries on after a pause. A running speed challenge starts its current stage over, so the
+    // time spent paused does not count towards it
+    pub fn resume(&mut self) {
+        self.run_steps = false;
+        if let Some(challenge) = &mut self.speed_challenge {
+            challenge.last_step = Instant::now();
+        }
+    }
+
+    // One press of Step: `step_size` instructions, stopping early at a breakpoint, watchpoint
+    // or anything else that would have paused a running program
+    pub fn step(&mut self) -> Result<(), CpuError> {
+        for _ in 0..self.step_size.max(1) {
+            let events = self.events_pushed;
+            self.progress()?;
+            if self.events_pushed != events {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // Undoes the last step, if it is still in the history. Settings changed since, like the mode
+    // and quirks, are kept
+    pub fn step_back(&mut self) {
+        if let Some(snapshot) = self.history.pop_back() {
+            self.cpu.restore(snapshot);
+            self.pc_history.pop_back();
+            self.update_sound();
+        }
+    }
+
+    pub fn reset_profile(&mut self) {
+        self.cpu.profile.clear();
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+        self.history.clear();
+        self.pc_history.clear();
+        self.timer_remainder = 0;
+        self.sound.set_active(false);
+        self.run_steps = true;
+    }
+
+    pub fn toggle_breakpoint(&mut self, address: u16) {
+        match self.breakpoints.iter().position(|a| *a == address) {
+            Some(i) => {
+                self.breakpoints.remove(i);
+            }
+            None => self.breakpoints.push(address),
+        }
+    }
+
+    // Runs until pc reaches the address, or something else pauses first
+    pub fn run_to(&mut self, address: u16) {
+        if !self.temporary_breakpoints.contains(&address) {
+            self.temporary_breakpoints.push(address);
+        }
+        self.resume();
+    }
+
+    // Runs the benchmark ROM through `progress` on a scratch emulator, so snapshots, the
+    // debugger checks and tracing are counted along with the instructions
+    pub fn measure_max_clock_rate(&self) -> u64 {
+        let mut emu = Emu::with_sound(Sound::silent());
+        emu.load_rom_bytes(BENCHMARK_ROM.to_vec())
+            .expect("benchmark ROM fits in memory");
+        emu.cpu.mode = self.cpu.mode;
+        emu.cpu.quirks = self.cpu.quirks;
+        emu.clock_rate = self.clock_rate;
+        emu.trace_enabled = self.trace_enabled;
+
+        let start = Instant::now();
+        for _ in 0..BENCHMARK_TICKS {
+            // The benchmark ROM loops forever, so it never halts
+            let _ = emu.progress();
+        }
+        let elapsed = start.elapsed().as_secs_f64().max(f64::EPSILON);
+
+        (BENCHMARK_TICKS as f64 / elapsed) as u64
+    }
+
+    pub fn exceeds_host_capacity(&self) -> bool {
+        self.clock_rate > self.max_clock_rate / 10 * 9
+    }
+
+    // Re-measured on startup and whenever the clock rate changes
+    pub fn check_host_capacity(&mut self) {
+        if self.clock_rate != self.capacity_checked_rate {
+            self.max_clock_rate = self.measure_max_clock_rate();
+        }
+
+        if self.auto_lower_clock_rate && self.exceeds_host_capacity() {
+            let clock_rate = self.max_clock_rate / 10 * 9;
+            self.log_config_change(format!(
+                "Clock rate changed: {} → {} Hz (host limit)",
+                self.clock_rate, clock_rate
+            ));
+            self.clock_rate = clock_rate;
+        }
+        self.capacity_checked_rate = self.clock_rate;
     }
 
-    pub fn progress(&mut self) {
-        self.cpu.tick();
-        if self.cpu.make_beep {
-            self.beep();
+    pub fn log_config_change(&mut self, description: impl Into<String>) {
+        // Wall clock time of day (UTC), so entries can be matched against other logs
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let entry = format!(
+            "{:02}:{:02}:{:02} {}",
+            secs / 3600 % 24,
+            secs / 60 % 60,
+            secs % 60,
+            description.into()
+        );
+
+        if self.config_changes.len() >= CONFIG_CHANGE_LOG_LEN {
+            self.config_changes.remove(0);
         }
+        self.config_changes.push((Instant::now(), entry));
+    }
+
+    pub fn record_frame_timing(&mut self, target_ms: u64, actual_ms: u64) {
+        if self.frame_timings.len() >= FRAME_TIMING_LOG_LEN {
+            self.frame_timings.pop_front();
+        }
+        self.frame_timings
+            .push_back((target_ms, actual_ms, actual_ms.abs_diff(target_ms)));
+    }
+
+    pub fn average_frame_time(&self) -> f64 {
+        if self.frame_timings.is_empty() {
+            return 0.0;
+        }
+        let total: u64 = self.frame_timings.iter().map(|(_, actual, _)| actual).sum();
+        total as f64 / self.frame_timings.len() as f64
+    }
+
+    pub fn frame_time_percentile(&self, percentile: f64) -> u64 {
+        let mut actuals = self
+            .frame_timings
+            .iter()
+            .map(|(_, actual, _)| *actual)
+            .collect::<Vec<_>>();
+        if actuals.is_empty() {
+            return 0;
+        }
+        actuals.sort_unstable();
+
+        let rank = (percentile / 100.0 * actuals.len() as f64).ceil() as usize;
+        actuals[rank.clamp(1, actuals.len()) - 1]
     }
 
-    pub fn draw(&self, frame: &mut [u8]) {
+    pub fn export_timing_log(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut csv = String::from("target_ms,actual_ms,delta_ms\n");
+        for (target, actual, delta) in &self.frame_timings {
+            writeln!(csv, "{},{},{}", target, actual, delta)?;
+        }
+        std::fs::write(path, csv)?;
+        Ok(())
+    }
+
+    pub fn export_trace_log(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut csv = String::from("pc,opcode,mnemonic");
+        for i in 0..16 {
+            write!(csv, ",v{:x}", i)?;
+        }
+        csv.push('\n');
+        for entry in &self.trace_log {
+            write!(
+                csv,
+                "{:04x},{:04x},\"{}\"",
+                entry.pc, entry.opcode, entry.mnemonic
+            )?;
+            for value in entry.registers {
+                write!(csv, ",{:02x}", value)?;
+            }
+            csv.push('\n');
+        }
+        std::fs::write(path, csv)?;
+        Ok(())
+    }
+
+    // Human-readable listing of the loaded program, with pc marked by `>`
+    pub fn save_trace(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut listing = String::new();
+        let instructions = self.rom.len().div_ceil(2);
+        for (address, opcode, mnemonic) in self.cpu.iter_instructions(0x200).take(instructions) {
+            let marker = if address == self.cpu.pc { '>' } else { ' ' };
+            writeln!(
+                listing,
+                "{} {:04X}  {:04x}  {}",
+                marker, address, opcode, mnemonic
+            )?;
+        }
+        std::fs::write(path, listing)?;
+        Ok(())
+    }
+
+    pub fn start_speed_challenge(
+        &mut self,
+        start_hz: u64,
+        step_hz: u64,
+        interval_secs: u64,
+        max_hz: u64,
+    ) {
+        let total_stages = match step_hz {
+            0 => 0,
+            _ => max_hz.saturating_sub(start_hz).div_ceil(step_hz),
+        };
+
+        self.clock_rate = start_hz;
+        self.speed_challenge = Some(SpeedChallenge {
+            stage: 0,
+            total_stages,
+            step_hz,
+            max_hz,
+            interval: Duration::from_secs(interval_secs),
+            last_step: Instant::now(),
+        });
+    }
+
+    pub fn update_speed_challenge(&mut self) {
+        if self.cpu.halted.is_some() {
+            self.speed_challenge = None;
+        }
+        let challenge = match self.speed_challenge.as_mut() {
+            Some(challenge) => challenge,
+            None => return,
+        };
+        if challenge.last_step.elapsed() < challenge.interval {
+            return;
+        }
+
+        if challenge.stage >= challenge.total_stages {
+            self.speed_challenge = None;
+            return;
+        }
+        challenge.stage += 1;
+        challenge.last_step = Instant::now();
+        self.clock_rate = (self.clock_rate + challenge.step_hz).min(challenge.max_hz);
+    }
+
+    // Instructions per second to run this frame, without changing the configured clock rate
+    pub fn frame_clock_rate(&self) -> u64 {
+        match self.boosted {
+            true => self.clock_rate.saturating_mul(BOOST_FACTOR),
+            false => self.clock_rate,
+        }
+    }
+
+    // Presentation mode does not change `scale`, so leaving it goes back to the chosen size
+    pub fn display_scale(&self) -> u32 {
+        if self.presentation_mode {
+            self.presentation_scale
+        } else {
+            self.scale
+        }
+    }
+
+    pub fn window_width(&self) -> u32 {
+        SCREEN_WIDTH * self.display_scale()
+    }
+
+    pub fn window_height(&self) -> u32 {
+        SCREEN_HEIGHT * self.display_scale()
+    }
+
+    // Largest whole scale that fits the window
+    pub fn fit_presentation_scale(&mut self, width: u32, height: u32) {
+        self.presentation_scale = (width / SCREEN_WIDTH).min(height / SCREEN_HEIGHT).max(1);
+    }
+
+    // `frame` is `window_width() * window_height()` RGBA pixels
+    pub fn draw(&mut self, frame: &mut [u8]) {
+        let colors = self.display_colors();
+        let (width, height) = (self.window_width() as usize, self.window_height() as usize);
+        let (gfx_width, gfx_height) = (self.cpu.gfx.width(), self.cpu.gfx.height());
+        for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
+            let x = (i % width) * gfx_width / width;
+            let y = (i / width) * gfx_height / height;
+
+            pixel.copy_from_slice(&colors[y * gfx_width + x]);
+        }
+        if self.show_grid {
+            self.draw_grid(frame);
+        }
+        if self.show_fps {
+            self.draw_fps(frame);
+        }
+        self.cpu.gfx_dirty = false;
+    }
+
+    // Lines along the top and left edge of every CHIP-8 pixel, on top of the display
+    fn draw_grid(&self, frame: &mut [u8]) {
+        let (width, height) = (self.window_width() as usize, self.window_height() as usize);
+        let (gfx_width, gfx_height) = (self.cpu.gfx.width(), self.cpu.gfx.height());
+        if width / gfx_width < MIN_GRID_CELL || height / gfx_height < MIN_GRID_CELL {
+            return;
+        }
+
+        // Same mapping as in `draw`, so the lines fall where the displayed pixel changes
+        let starts_cell = |i: usize, size: usize, gfx_size: usize| {
+            i > 0 && i * gfx_size / size != (i - 1) * gfx_size / size
+        };
         for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let x = (i % WINDOW_WIDTH as usize) / 16;
-            let y = (i / WINDOW_WIDTH as usize) / 16;
+            let (x, y) = (i % width, i / width);
+            if starts_cell(x, width, gfx_width) || starts_cell(y, height, gfx_height) {
+                pixel.copy_from_slice(&self.grid_color);
+            }
+        }
+    }
+
+    // Writes the FPS in the top left corner of the window, using the built-in font. Whatever
+    // does not fit in a small window is cut off
+    fn draw_fps(&self, frame: &mut [u8]) {
+        const MARGIN: usize = 8;
+        let (width, height) = (self.window_width() as usize, self.window_height() as usize);
+        let fps = format!("{:.0}", self.fps());
+        for (i, digit) in fps.chars().filter_map(|c| c.to_digit(10)).enumerate() {
+            let sprite = &CHARACTER_SPRITES[digit as usize * 5..][..5];
+            for (row, bits) in sprite.iter().enumerate() {
+                for col in (0..4).filter(|col| bits & (0x80 >> col) != 0) {
+                    let x = MARGIN + (i * 5 + col) * FPS_DIGIT_SCALE;
+                    let y = MARGIN + row * FPS_DIGIT_SCALE;
+                    if x + FPS_DIGIT_SCALE > width || y + FPS_DIGIT_SCALE > height {
+                        continue;
+                    }
+                    for py in y..(y + FPS_DIGIT_SCALE) {
+                        let start = (py * width + x) * 4;
+                        for pixel in frame[start..(start + FPS_DIGIT_SCALE * 4)].chunks_exact_mut(4)
+                        {
+                            pixel.copy_from_slice(&[0x00, 0xff, 0x00, 0xff]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Called once per rendered frame
+    pub fn record_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame_at {
+            if self.frame_durations.len() >= FPS_SAMPLES {
+                self.frame_durations.pop_front();
+            }
+            self.frame_durations.push_back(now - last);
+        }
+        self.last_frame_at = Some(now);
+    }
+
+    // Averaged over the last FPS_SAMPLES frames
+    pub fn fps(&self) -> f64 {
+        let total: Duration = self.frame_durations.iter().sum();
+        if total.is_zero() {
+            return 0.0;
+        }
+        self.frame_durations.len() as f64 / total.as_secs_f64()
+    }
+
+    // Colour of every CHIP-8 pixel, row-major. With a ghost trail, pixels that were lit in the
+    // last `ghost_frames` frames fade from their old colour to the background
+    fn display_colors(&mut self) -> Vec<[u8; 4]> {
+        let (width, height) = (self.cpu.gfx.width(), self.cpu.gfx.height());
+        let planes = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| self.cpu.gfx.get(x, y) as usize | (self.cpu.gfx2.get(x, y) as usize) << 1)
+            .collect::<Vec<_>>();
+
+        // A change of resolution leaves nothing to fade from
+        self.gfx_history.retain(|frame| frame.len() == planes.len());
+        while self.gfx_history.len() > self.ghost_frames as usize {
+            self.gfx_history.pop_front();
+        }
 
-            let on = self.cpu.gfx[y * 64 + x];
+        let trail = self.ghost_frames as u32 + 1;
+        let background = self.palette[0];
+        let colors = planes
+            .iter()
+            .enumerate()
+            .map(|(i, &current)| {
+                if current != 0 {
+                    return self.palette[current];
+                }
+                // Frames ago the pixel was last lit, and the colour it had then
+                let lit = self
+                    .gfx_history
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .find(|(_, frame)| frame[i] != 0)
+                    .map(|(age, frame)| (age as u32 + 1, self.palette[frame[i]]));
+                match lit {
+                    Some((age, old)) => {
+                        let mut color = background;
+                        for (channel, from) in color.iter_mut().zip(old) {
+                            *channel = ((from as u32 * (trail - age) + *channel as u32 * age)
+                                / trail) as u8;
+                        }
+                        color
+                    }
+                    None => background,
+                }
+            })
+            .collect();
 
-            let rgba = if on {
-                [0xff, 0xff, 0xff, 0xff]
-            } else {
-                [0x11, 0x11, 0x11, 0xff]
-            };
+        if self.ghost_frames > 0 {
+            if self.gfx_history.len() >= self.ghost_frames as usize {
+                self.gfx_history.pop_front();
+            }
+            self.gfx_history.push_back(planes);
+        }
+        colors
+    }
+
+    // Mixes both bitplanes into a palette colour
+    pub fn pixel_color(&self, x: usize, y: usize) -> [u8; 4] {
+        let planes = self.cpu.gfx.get(x, y) as usize | (self.cpu.gfx2.get(x, y) as usize) << 1;
+        self.palette[planes]
+    }
+
+    // Encodes the display as a greyscale PNG, one pixel per CHIP-8 pixel
+    pub fn screenshot(&self) -> Result<Vec<u8>> {
+        let (width, height) = (self.cpu.gfx.width(), self.cpu.gfx.height());
+        let luma = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let [r, g, b, _] = self.pixel_color(x, y);
+                ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+            })
+            .collect::<Vec<_>>();
 
-            pixel.copy_from_slice(&rgba);
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png).write_image(&luma, width as u32, height as u32, ColorType::L8)?;
+        Ok(png)
+    }
+
+    // Named after the ROM and the frame, e.g. `pong_1650000000_1234.png`
+    pub fn save_screenshot(&self) -> Result<String> {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let path = format!(
+            "{}_{}_{}.png",
+            self.rom_name.as_deref().unwrap_or("untitled"),
+            secs,
+            self.total_frames
+        );
+        std::fs::write(&path, self.screenshot()?)?;
+        Ok(path)
+    }
+
+    pub fn apply_config(&mut self, config: &Config) {
+        self.clock_rate = config.clock_rate;
+        self.frame_skip = config.frame_skip;
+        self.ghost_frames = config.ghost_frames.min(MAX_GHOST_FRAMES);
+        self.show_grid = config.show_grid;
+        self.grid_color = config.grid_color;
+        self.fullscreen = config.fullscreen;
+        self.scale = config.scale.clamp(1, MAX_SCALE);
+        self.auto_lower_clock_rate = config.auto_lower_clock_rate;
+        self.cpu.mode = config.mode;
+        self.cpu.quirks = config.quirks;
+        self.palette = config.palette;
+        self.key_map = config.key_map;
+        self.step_key = config.step_key;
+        self.boost_key = config.boost_key;
+        self.sound_volume = config.sound_volume.clamp(0.0, 1.0);
+        self.sound_muted = config.sound_muted;
+        self.sound.set_volume(self.output_volume());
+        self.config_changes = config
+            .config_changes
+            .iter()
+            .map(|entry| (Instant::now(), entry.clone()))
+            .collect();
+    }
+
+    // Window layout is left for the GUI to fill in
+    pub fn config(&self) -> Config {
+        Config {
+            clock_rate: self.clock_rate,
+            frame_skip: self.frame_skip,
+            ghost_frames: self.ghost_frames,
+            show_grid: self.show_grid,
+            grid_color: self.grid_color,
+            fullscreen: self.fullscreen,
+            scale: self.scale,
+            auto_lower_clock_rate: self.auto_lower_clock_rate,
+            mode: self.cpu.mode,
+            palette: self.palette,
+            key_map: self.key_map,
+            step_key: self.step_key,
+            boost_key: self.boost_key,
+            sound_volume: self.sound_volume,
+            sound_muted: self.sound_muted,
+            config_changes: self
+                .config_changes
+                .iter()
+                .map(|(_, entry)| entry.clone())
+                .collect(),
+            quirks: self.cpu.quirks,
+            ..Config::new()
         }
     }
 
-    pub fn beep(&mut self) {
-        self.cpu.make_beep = false;
-        println!("BEEP"); // TODO
+    pub fn save_state(&self) -> EmuState {
+        EmuState::new(&self.cpu, self.clock_rate)
+    }
+
+    pub fn load_state(&mut self, state: EmuState) -> Result<()> {
+        state.restore_into(&mut self.cpu)?;
+        self.history.clear();
+        if self.clock_rate != state.clock_rate {
+            self.log_config_change(format!(
+                "Clock rate changed: {} → {} Hz (save state)",
+                self.clock_rate, state.clock_rate
+            ));
+            self.clock_rate = state.clock_rate;
+        }
+        Ok(())
     }
 
-    pub fn load_rom(&mut self, path: &str) -> Result<()> {
-        let rom_bytes = std::fs::read(path)?;
-        self.cpu.memory[0x200..(0x200 + rom_bytes.len())].copy_from_slice(&rom_bytes);
+    pub fn load_rom(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let rom_bytes = match std::fs::read(path) {
+            Ok(rom_bytes) => rom_bytes,
+            Err(e) => {
+                self.console
+                    .push(format!("Failed to read {}: {}", path.display(), e));
+                return Err(e.into());
+            }
+        };
+        self.load_rom_bytes(rom_bytes)?;
+        self.rom_name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned());
         Ok(())
     }
+
+    // Compiles Octo source and loads the result like a ROM
+    pub fn load_octo(&mut self, source: &str) -> Result<()> {
+        let rom = octo::compile(source)?;
+        self.load_rom_bytes(rom)
+    }
+
+    pub fn load_rom_bytes(&mut self, rom_bytes: Vec<u8>) -> Result<()> {
+        let result = self.replace_rom(rom_bytes);
+        if let Err(e) = &result {
+            self.console.push(format!("Failed to load ROM: {}", e));
+        }
+        result
+    }
+
+    fn replace_rom(&mut self, rom_bytes: Vec<u8>) -> Result<()> {
+        self.cpu.load_bytes(&rom_bytes)?;
+        self.cpu.reset();
+        self.rom_warning = Chip8::validate_rom(&rom_bytes).err();
+        self.history.clear();
+        self.pc_history.clear();
+        self.events.clear();
+
+        self.initial_rom_checksum = self.rom_checksum(rom_bytes.len());
+        self.last_rom_checksum = self.initial_rom_checksum;
+        let rom_info = RomInfo::new(&rom_bytes)?;
+        if let Some(metadata) = &rom_info.metadata {
+            self.apply_quirks_preset(metadata.quirks_preset);
+        }
+        self.rom_info = Some(rom_info);
+        self.rom = rom_bytes;
+        self.rom_name = None;
+        Ok(())
+    }
+
+    // A font file is the 16 4x5 sprites, 80 bytes
+    pub fn load_font_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        let font = data.as_slice().try_into().map_err(|_| {
+            eyre!(
+                "font is {} bytes, expected {}",
+                data.len(),
+                CHARACTER_SPRITES.len()
+            )
+        })?;
+        self.cpu.load_font(font);
+        self.log_config_change(format!("Font loaded: {}", path.display()));
+        Ok(())
+    }
+
+    fn apply_quirks_preset(&mut self, preset: QuirksPreset) {
+        self.cpu.quirks = Quirks::from(preset);
+        self.cpu.mode = match preset {
+            QuirksPreset::SuperChip => Mode::SuperChip,
+            _ => Mode::Chip8,
+        };
+        self.log_config_change(format!("Quirks preset applied: {}", preset.name()));
+    }
+
+    // Command line arguments take precedence over the config file and the ROM catalog
+    pub fn apply_args(&mut self, args: &Args) -> Result<()> {
+        if let Some(path) = args.rom() {
+            self.load_rom(path)?;
+        }
+        if let Some(clock_rate) = args.clock_rate {
+            self.clock_rate = clock_rate;
+        }
+        if let Some(scale) = args.scale {
+            self.scale = scale;
+        }
+        if args.fullscreen {
+            self.fullscreen = true;
+        }
+        if let Some(quirks) = args.quirks {
+            self.apply_quirks_preset(quirks.into());
+        }
+        if let Some(path) = &args.font {
+            self.load_font_file(path)?;
+        }
+        if let Some(seed) = args.seed {
+            self.cpu.seed_rng(seed);
+        }
+        Ok(())
+    }
+
+    fn rom_checksum(&self, rom_len: usize) -> u32 {
+        self.cpu.memory_checksum(0x200, (0x200 + rom_len) as u16)
+    }
+
+    pub fn check_self_modification(&mut self) {
+        if !self.detect_self_modification {
+            return;
+        }
+
+        let checksum = self.rom_checksum(self.rom.len());
+        if checksum == self.last_rom_checksum {
+            return;
+        }
+        // Only reported once per change, as the checksum is compared against the last one
+        self.last_rom_checksum = checksum;
+
+        // The write that just happened is the one that changed the ROM, unless the program
+        // changed it through some other instruction
+        let rom_end = 0x200 + self.rom.len() as u16;
+        let written = self
+            .last_memory_write
+            .as_ref()
+            .map(|(range, _)| range.start.max(0x200))
+            .filter(|address| *address < rom_end);
+        let modified = written.or_else(|| {
+            self.cpu.memory[0x200..]
+                .iter()
+                .zip(&self.rom)
+                .position(|(current, original)| current != original)
+                .map(|offset| 0x200 + offset as u16)
+        });
+        if let Some(address) = modified {
+            self.push_event(EmuEvent::RomModified { address });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emu_with_rom(rom: &[u8]) -> Emu {
+        let mut emu = Emu::with_sound(Sound::silent());
+        emu.load_rom_bytes(rom.to_vec()).unwrap();
+        emu
+    }
+
+    #[test]
+    fn unknown_opcodes_halt_and_log() {
+        // 0nnn, 00FB outside SUPER-CHIP and 5xy1
+        for opcode in [0x0123u16, 0x00FB, 0x5121] {
+            let mut emu = emu_with_rom(&opcode.to_be_bytes());
+            assert!(emu.progress().is_err());
+            assert_eq!(emu.cpu.halted, Some(CpuError::UnknownOpcode(0x200, opcode)));
+            assert!(matches!(emu.events.back(), Some(EmuEvent::Halted { .. })));
+            let line = emu.console.lines.back().unwrap();
+            assert!(
+                line.contains(&format!("unknown opcode {:04x}", opcode)),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn step_back_keeps_settings() {
+        // LD V1, 05; ADD V1, 01
+        let mut emu = emu_with_rom(&[0x61, 0x05, 0x71, 0x01]);
+        emu.progress().unwrap();
+        emu.progress().unwrap();
+        emu.cpu.mode = Mode::SuperChip;
+        emu.cpu.quirks.vf_reset = true;
+
+        emu.step_back();
+        assert_eq!((emu.cpu.pc, emu.cpu.V[1]), (0x202, 5));
+        emu.step_back();
+        assert_eq!((emu.cpu.pc, emu.cpu.V[1]), (0x200, 0));
+        assert!(emu.history.is_empty());
+        assert_eq!(emu.cpu.mode, Mode::SuperChip);
+        assert!(emu.cpu.quirks.vf_reset);
+    }
+
+    #[test]
+    fn self_modification_reported_once_per_write() {
+        #[rustfmt::skip]
+        let mut emu = emu_with_rom(&[
+            0xA2, 0x0E, // LD   I, 20e
+            0x60, 0x77, // LD   V0, 77
+            0xF0, 0x55, // LD   [I], V0
+            0xA2, 0x0F, // LD   I, 20f
+            0xF0, 0x55, // LD   [I], V0
+            0x12, 0x0A, // JP   20a
+            0x00, 0x00, 0x00, 0x00,
+        ]);
+        emu.detect_self_modification = true;
+        let modified = |emu: &Emu| {
+            emu.events
+                .iter()
+                .filter_map(|event| match event {
+                    EmuEvent::RomModified { address } => Some(*address),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for _ in 0..3 {
+            emu.progress().unwrap();
+        }
+        emu.check_self_modification();
+        emu.check_self_modification();
+        assert_eq!(modified(&emu), [0x20E]);
+
+        for _ in 0..2 {
+            emu.progress().unwrap();
+        }
+        emu.check_self_modification();
+        assert_eq!(modified(&emu), [0x20E, 0x20F]);
+    }
+
+    #[test]
+    fn events_are_capped() {
+        // JP 200, with a breakpoint on it
+        let mut emu = emu_with_rom(&[0x12, 0x00]);
+        emu.toggle_breakpoint(0x200);
+        for _ in 0..(EVENT_LOG_LEN + 10) {
+            emu.progress().unwrap();
+        }
+        assert_eq!(emu.events.len(), EVENT_LOG_LEN);
+    }
+
+    #[test]
+    fn headless_run_reports_halts() {
+        // LD V0, 01; a 5xy1 that is not an instruction
+        let mut emu = emu_with_rom(&[0x60, 0x01, 0x51, 0x21]);
+        let result = emu.run_headless(100);
+        assert_eq!(result.halted, Some(CpuError::UnknownOpcode(0x202, 0x5121)));
+        assert_eq!(result.cycles, 2);
+        assert_eq!(result.registers[0], 1);
+
+        // RET with nothing to return to
+        let mut emu = emu_with_rom(&[0x00, 0xEE]);
+        let result = emu.run_headless(100);
+        assert_eq!(result.halted, Some(CpuError::StackUnderflow(0x200)));
+
+        // JP 200
+        let mut emu = emu_with_rom(&[0x12, 0x00]);
+        let result = emu.run_headless(100);
+        assert_eq!((result.halted, result.cycles), (None, 100));
+    }
+
+    #[test]
+    fn exit_stops_headless_run_and_speed_challenge() {
+        // HIGH; EXIT
+        let mut emu = emu_with_rom(&[0x00, 0xFF, 0x00, 0xFD]);
+        emu.cpu.mode = Mode::SuperChip;
+        let result = emu.run_headless(100);
+        assert_eq!(result.halted, Some(CpuError::Exited(0x202)));
+        assert_eq!(result.cycles, 2);
+
+        let mut emu = emu_with_rom(&[0x00, 0xFD]);
+        emu.cpu.mode = Mode::SuperChip;
+        emu.start_speed_challenge(600, 50, 5, 2000);
+        assert_eq!(emu.progress(), Err(CpuError::Exited(0x200)));
+        assert!(emu.speed_challenge.is_none());
+        let _ = emu.progress();
+        assert_eq!(emu.cpu.pc, 0x200);
+    }
+
+    #[test]
+    fn speed_challenge_stops_on_halt_and_warns_on_pause() {
+        // JP 202 with a breakpoint on 202, then a RET with nothing to return to
+        let mut emu = emu_with_rom(&[0x12, 0x02, 0x00, 0xEE]);
+        emu.toggle_breakpoint(0x202);
+        emu.start_speed_challenge(600, 50, 5, 2000);
+
+        emu.progress().unwrap();
+        assert!(emu.speed_challenge.is_some());
+        assert!(matches!(
+            emu.events.back(),
+            Some(EmuEvent::SpeedChallengePaused { clock_rate: 600 })
+        ));
+
+        // Time spent paused does not count towards the stage
+        let paused_at = emu.speed_challenge.as_ref().unwrap().last_step;
+        std::thread::sleep(Duration::from_millis(1));
+        emu.resume();
+        assert!(emu.speed_challenge.as_ref().unwrap().last_step > paused_at);
+        assert!(!emu.run_steps);
+
+        assert!(emu.progress().is_err());
+        assert!(emu.speed_challenge.is_none());
+        assert_eq!(
+            emu.console.lines.back().unwrap(),
+            "Speed challenge stopped at 600 Hz"
+        );
+    }
+
+    #[test]
+    fn benchmark_leaves_the_running_rom_alone() {
+        // JP 200
+        let mut emu = emu_with_rom(&[0x12, 0x00]);
+        assert!(emu.measure_max_clock_rate() > 0);
+        assert_eq!(emu.cpu.pc, 0x200);
+        assert!(emu.history.is_empty());
+        emu.progress().unwrap();
+        assert_eq!(emu.total_cycles, 1);
+    }
+
+    #[test]
+    fn timers_count_at_60_hz() {
+        // JP 200
+        let mut emu = emu_with_rom(&[0x12, 0x00]);
+        emu.clock_rate = 600;
+        emu.cpu.delay_timer = 255;
+
+        // A sixth of a second at 600 Hz
+        for _ in 0..100 {
+            emu.progress().unwrap();
+        }
+        assert_eq!(emu.cpu.delay_timer, 255 - 10);
+
+        // And the rest of the second
+        for _ in 0..500 {
+            emu.progress().unwrap();
+        }
+        assert_eq!(emu.cpu.delay_timer, 255 - 60);
+    }
+
+    #[test]
+    fn frame_timer_does_not_drift() {
+        let mut timer = FrameTimer::new();
+        let steps = (0..60).map(|_| timer.steps(700)).collect::<Vec<_>>();
+        assert_eq!(steps.iter().sum::<u64>(), 700);
+        assert!(steps.iter().all(|steps| matches!(steps, 11 | 12)));
+    }
+
+    #[test]
+    fn draw_at_runtime_scale() {
+        for scale in [1, 4] {
+            let mut emu = Emu::with_sound(Sound::silent());
+            emu.scale = scale;
+            emu.cpu.gfx.set(3, 2, true);
+            let (width, height) = (emu.window_width() as usize, emu.window_height() as usize);
+            let mut frame = vec![0; width * height * 4];
+            emu.draw(&mut frame);
+
+            let scale = scale as usize;
+            for (i, pixel) in frame.chunks(4).enumerate() {
+                let (x, y) = (i % width, i / width);
+                let lit = x / scale == 3 && y / scale == 2;
+                let color = DEFAULT_PALETTE[lit as usize];
+                assert_eq!(pixel, color, "({}, {}) at scale {}", x, y, scale);
+            }
+        }
+    }
 }
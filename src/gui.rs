@@ -1,63 +1,1746 @@
-use egui::{Align2, ClippedMesh, Color32, Grid, TexturesDelta};
+use std::{
+    f64::consts::TAU,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use egui::{
+    plot::{Bar, BarChart, Line, Plot, Value, Values},
+    pos2,
+    text::LayoutJob,
+    vec2, Align, Align2, ClippedMesh, Color32, ColorImage, FontId, Grid, Key, RichText, Stroke,
+    TextFormat, TextureHandle, TexturesDelta,
+};
 use egui_wgpu_backend::{BackendError, RenderPass, ScreenDescriptor};
 use pixels::wgpu;
-use winit::window::Window;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::{SyntaxDefinition, SyntaxSet, SyntaxSetBuilder},
+    util::LinesWithEndings,
+};
+use winit::{
+    event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent},
+    window::Window,
+};
+
+use cchipt::{
+    audio::{AudioPattern, Sound, TONE_FREQUENCY},
+    chip8::{
+        assembler::{self, AssembleError},
+        diff_roms, Chip8, Compatibility, CompatibilityMatrix, CpuError, DiffKind, Mode, Quirks,
+        QuirksPreset, RomDiffEntry,
+    },
+    config::Config,
+    emu::{
+        ConditionalBreakpoint, Emu, EmuEvent, WatchKind, BOOST_FACTOR, CHARACTER_SPRITES,
+        DEFAULT_CLOCK_RATE, DEFAULT_KEY_MAP, DEFAULT_PALETTE, HEX_PAD_KEY_MAP, MAX_GHOST_FRAMES,
+        PC_HISTORY_LEN, REFRESH_RATE, THIN_CHARACTER_SPRITES,
+    },
+    state::EmuState,
+};
+
+// How long the "ROM loaded" notice stays on screen
+const TOAST_DURATION: Duration = Duration::from_secs(2);
+
+// How long memory written by the program stays highlighted in the memory viewer
+const WRITE_HIGHLIGHT_CYCLES: u64 = 100;
+
+// The Sound window shows this much of the waveform, in seconds
+const WAVEFORM_DURATION: f64 = 0.01;
+const WAVEFORM_POINTS: usize = 200;
+
+// ROM browser thumbnails show the display after this many instructions
+const THUMBNAIL_CYCLES: u64 = 5000;
+const THUMBNAIL_SIZE: [f32; 2] = [192.0, 96.0];
+
+const AUTHOR_TEMPLATE: &str = "\
+; Draw the digit in V0 at (V1, V2)
+start:
+    LD   V0, 0a
+    LD   V1, 1c
+    LD   V2, 0d
+    LD   F, V0
+    DRW  V1, V2, 5
+loop:
+    JP   loop
+";
+
+struct Highlighter {
+    syntax_set: SyntaxSet,
+    theme: Theme,
+}
+
+impl Highlighter {
+    fn new() -> Self {
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add(
+            SyntaxDefinition::load_from_str(
+                include_str!("../assets/chip8.sublime-syntax"),
+                true,
+                None,
+            )
+            .expect("bundled CHIP-8 syntax should be valid"),
+        );
+        let theme = ThemeSet::load_defaults()
+            .themes
+            .remove("base16-ocean.dark")
+            .expect("default themes should include base16-ocean.dark");
+
+        Self {
+            syntax_set: builder.build(),
+            theme,
+        }
+    }
+
+    fn highlight(&self, source: &str, errors: &[AssembleError]) -> LayoutJob {
+        let syntax = &self.syntax_set.syntaxes()[0];
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        let mut job = LayoutJob::default();
+
+        for (line, text) in LinesWithEndings::from(source).enumerate() {
+            let underline = if errors.iter().any(|e| e.line == line) {
+                Stroke::new(1.0, Color32::RED)
+            } else {
+                Stroke::none()
+            };
+            let format = |color| TextFormat {
+                font_id: FontId::monospace(14.0),
+                color,
+                underline,
+                ..Default::default()
+            };
+
+            match highlighter.highlight_line(text, &self.syntax_set) {
+                Ok(ranges) => {
+                    for (style, range) in ranges {
+                        let fg = style.foreground;
+                        job.append(range, 0.0, format(Color32::from_rgb(fg.r, fg.g, fg.b)));
+                    }
+                }
+                Err(_) => job.append(text, 0.0, format(Color32::GRAY)),
+            }
+        }
+        job
+    }
+}
+
+struct RomBrowserEntry {
+    path: PathBuf,
+    thumbnail: Option<Result<TextureHandle, String>>, // None until it has been rendered
+}
+
+// The .ch8 files in a directory, by name
+fn scan_rom_directory(dir: &Path) -> std::io::Result<Vec<RomBrowserEntry>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            matches!(path.extension(), Some(extension) if extension.eq_ignore_ascii_case("ch8"))
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    Ok(paths
+        .into_iter()
+        .map(|path| RomBrowserEntry {
+            path,
+            thumbnail: None,
+        })
+        .collect())
+}
+
+// Runs the ROM on its own silent emulator, so whatever is loaded is left alone
+fn rom_thumbnail(path: &Path) -> color_eyre::Result<ColorImage> {
+    let mut emu = Emu::with_sound(Sound::silent());
+    emu.load_rom(path)?;
+    let result = emu.run_headless(THUMBNAIL_CYCLES);
+    Ok(ColorImage {
+        size: [result.gfx.width(), result.gfx.height()],
+        pixels: result
+            .gfx
+            .pixels()
+            .iter()
+            .map(|&on| {
+                let [r, g, b, a] = emu.palette[on as usize];
+                Color32::from_rgba_unmultiplied(r, g, b, a)
+            })
+            .collect(),
+    })
+}
 
-use crate::{chip8::Chip8, emu::Emu};
 struct Gui {
     show_run_controls: bool,
+    show_debug: bool,
+    show_settings_history: bool,
+    show_console: bool,
+    show_display: bool,
+    show_quirks: bool,
+    show_key_mapping: bool,
+    show_rom_info: bool,
+    show_performance: bool,
+    show_compatibility: bool,
+    show_rom_diff: bool,
     show_cpu_state: bool,
+    show_disassembly: bool,
+    show_trace: bool,
+    show_profiler: bool,
+    show_sound: bool,
+    show_about: bool,
+    show_rom_browser: bool,
+    rom_browser: Vec<RomBrowserEntry>,
+    rom_browser_dir: Option<PathBuf>, // Directory `rom_browser` was scanned from
+    rom_browser_error: Option<String>,
+    quit_requested: bool, // Picked from the File menu, the event loop does the quitting
     show_memory: bool,
     show_gfx: bool,
+    gfx_texture: Option<TextureHandle>,
+    gfx_zoom: f32,
+    author_mode: bool,
+    speed_challenge_step_hz: u64,
+    speed_challenge_interval: u64, // Seconds between steps
+    speed_challenge_max_hz: u64,
+    author_source: String,
+    author_errors: Vec<AssembleError>,
+    highlighter: Highlighter,
+    compatibility: CompatibilityMatrix,
+    compatibility_selection: Option<(usize, usize)>,
+    diff_path: String,
+    rom_path: String,
+    rom_directory: Option<PathBuf>,
+    state_path: String,
+    breakpoint_input: String,
+    watchpoint_input: String,
+    condition_input: String,
+    condition_error: Option<String>,
+    watchpoint_kind: WatchKind,
+    key_binding: Option<usize>, // CHIP-8 key waiting for a host key
+    binding_step_key: bool,
+    binding_boost_key: bool,
+    captured_key: Option<VirtualKeyCode>, // Host key pressed while rebinding
+    clock_rate_drag_start: Option<u64>,
+    ghost_frames_drag_start: Option<u8>,
+    disassembly_pc: Option<u16>,
+    memory_edit_mode: bool,
+    memory_edit: Option<(usize, String)>,
+    memory_search: String,
+    memory_match: Option<(u16, usize)>, // Address and length of the last search hit
+    memory_search_error: Option<String>,
+    scroll_to_match: bool,
+    rom_error: Option<String>,
+    error_dialog: Option<String>, // Shown in the Error window until dismissed
+    toast: Option<(Instant, String)>,
+    state_error: Option<String>,
+    diff_error: Option<String>,
+    rom_diff: Vec<RomDiffEntry>,
 }
 
 impl Gui {
     fn new() -> Self {
         Self {
             show_run_controls: true,
+            show_debug: true,
+            show_settings_history: false,
+            show_console: false,
+            show_display: true,
+            show_quirks: false,
+            show_key_mapping: false,
+            show_rom_info: true,
+            show_performance: true,
+            show_compatibility: false,
+            show_rom_diff: false,
             show_cpu_state: true,
+            show_disassembly: false,
+            show_trace: false,
+            show_profiler: false,
+            show_sound: false,
+            show_about: false,
+            show_rom_browser: false,
+            rom_browser: Vec::new(),
+            rom_browser_dir: None,
+            rom_browser_error: None,
+            quit_requested: false,
             show_memory: true,
             show_gfx: true,
+            gfx_texture: None,
+            gfx_zoom: 4.0,
+            author_mode: false,
+            speed_challenge_step_hz: 50,
+            speed_challenge_interval: 5,
+            speed_challenge_max_hz: 2000,
+            author_source: AUTHOR_TEMPLATE.to_string(),
+            author_errors: Vec::new(),
+            highlighter: Highlighter::new(),
+            compatibility: CompatibilityMatrix::new(),
+            compatibility_selection: None,
+            diff_path: String::new(),
+            rom_path: String::new(),
+            rom_directory: None,
+            state_path: String::from("save.cchipt"),
+            breakpoint_input: String::new(),
+            watchpoint_input: String::new(),
+            condition_input: String::new(),
+            condition_error: None,
+            watchpoint_kind: WatchKind::Write,
+            key_binding: None,
+            binding_step_key: false,
+            binding_boost_key: false,
+            captured_key: None,
+            clock_rate_drag_start: None,
+            ghost_frames_drag_start: None,
+            disassembly_pc: None,
+            memory_edit_mode: false,
+            memory_edit: None,
+            memory_search: String::new(),
+            memory_match: None,
+            memory_search_error: None,
+            scroll_to_match: false,
+            rom_error: None,
+            error_dialog: None,
+            toast: None,
+            state_error: None,
+            diff_error: None,
+            rom_diff: Vec::new(),
+        }
+    }
+
+    fn windows(&mut self) -> [(&'static str, &mut bool); 18] {
+        [
+            ("Run Controls", &mut self.show_run_controls),
+            ("Debug", &mut self.show_debug),
+            ("Settings History", &mut self.show_settings_history),
+            ("Console", &mut self.show_console),
+            ("Display", &mut self.show_display),
+            ("Quirks", &mut self.show_quirks),
+            ("Key Mapping", &mut self.show_key_mapping),
+            ("ROM Info", &mut self.show_rom_info),
+            ("Performance", &mut self.show_performance),
+            ("Compatibility", &mut self.show_compatibility),
+            ("ROM Diff", &mut self.show_rom_diff),
+            ("CPU State", &mut self.show_cpu_state),
+            ("Disassembly", &mut self.show_disassembly),
+            ("Trace", &mut self.show_trace),
+            ("Profiler", &mut self.show_profiler),
+            ("Sound", &mut self.show_sound),
+            ("Memory", &mut self.show_memory),
+            ("GFX", &mut self.show_gfx),
+        ]
+    }
+
+    fn apply_config(&mut self, config: &Config) {
+        self.rom_directory = config.rom_directory.clone();
+        for (title, open) in self.windows() {
+            if let Some(value) = config.windows.get(title) {
+                *open = *value;
+            }
+        }
+    }
+
+    fn save_config(&mut self, emu: &Emu) {
+        let mut config = emu.config();
+        config.windows = self
+            .windows()
+            .into_iter()
+            .map(|(title, open)| (title.to_string(), *open))
+            .collect();
+        config.rom_directory = self.rom_directory.clone();
+        if let Err(e) = config.save() {
+            eprintln!("Failed to save config: {}", e);
+        }
+    }
+
+    fn handle_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+            ..
+        } = event
+        {
+            if self.key_binding.is_some() || self.binding_step_key || self.binding_boost_key {
+                self.captured_key = Some(*key);
+            }
+        }
+    }
+
+    // Failures end up in the error dialog, the return value only says whether it loaded
+    fn load_rom(&mut self, emu: &mut Emu, path: &Path) -> bool {
+        match emu.load_rom(path) {
+            Ok(()) => {
+                self.rom_directory = path.parent().map(Path::to_path_buf);
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                self.toast = Some((
+                    Instant::now(),
+                    format!("ROM loaded: {}", name.to_string_lossy()),
+                ));
+                true
+            }
+            Err(e) => {
+                self.error_dialog = Some(format!("Failed to load {}: {}", path.display(), e));
+                false
+            }
+        }
+    }
+
+    fn open_rom_dialog(&mut self, emu: &mut Emu) {
+        let mut dialog = rfd::FileDialog::new().add_filter("CHIP-8 ROM", &["ch8", "rom"]);
+        if let Some(dir) = &self.rom_directory {
+            dialog = dialog.set_directory(dir);
+        }
+        if let Some(path) = dialog.pick_file() {
+            self.load_rom(emu, &path);
+        }
+    }
+
+    fn save_state_dialog(&mut self, emu: &Emu) {
+        let dialog = rfd::FileDialog::new()
+            .add_filter("cchipt state", &["cchipt"])
+            .set_file_name(&self.state_path);
+        if let Some(path) = dialog.save_file() {
+            if let Err(e) = emu.save_state().write(&path) {
+                self.error_dialog = Some(format!("Failed to save {}: {}", path.display(), e));
+            }
+        }
+    }
+
+    fn load_state_dialog(&mut self, emu: &mut Emu) {
+        let dialog = rfd::FileDialog::new().add_filter("cchipt state", &["cchipt"]);
+        if let Some(path) = dialog.pick_file() {
+            if let Err(e) = EmuState::read(&path).and_then(|state| emu.load_state(state)) {
+                self.error_dialog = Some(format!("Failed to load {}: {}", path.display(), e));
+            }
+        }
+    }
+
+    // Fills the window, over the display, until a ROM is picked or it is closed
+    fn rom_browser(&mut self, ctx: &egui::Context, emu: &mut Emu) {
+        let dir = self
+            .rom_directory
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("."));
+        if self.rom_browser_dir.as_ref() != Some(&dir) {
+            match scan_rom_directory(&dir) {
+                Ok(entries) => {
+                    self.rom_browser = entries;
+                    self.rom_browser_error = None;
+                }
+                Err(e) => {
+                    self.rom_browser.clear();
+                    self.rom_browser_error =
+                        Some(format!("Failed to read {}: {}", dir.display(), e));
+                }
+            }
+            self.rom_browser_dir = Some(dir.clone());
+        }
+
+        // One thumbnail a frame, so the browser shows up straight away and fills in as it goes
+        if let Some(entry) = self.rom_browser.iter_mut().find(|e| e.thumbnail.is_none()) {
+            let thumbnail = rom_thumbnail(&entry.path)
+                .map(|image| ctx.load_texture(entry.path.to_string_lossy(), image))
+                .map_err(|e| e.to_string());
+            entry.thumbnail = Some(thumbnail);
+            ctx.request_repaint();
+        }
+
+        let mut picked = None;
+        let mut choose_folder = false;
+        let mut close = false;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("ROM Browser");
+                ui.label(dir.display().to_string());
+                choose_folder = ui.button("Choose Folder…").clicked();
+                close = ui.button("Close").clicked();
+            });
+            if let Some(error) = &self.rom_browser_error {
+                ui.colored_label(Color32::RED, error);
+            } else if self.rom_browser.is_empty() {
+                ui.label("No .ch8 files in this folder");
+            }
+
+            ui.separator();
+
+            let columns = (ui.available_width() / (THUMBNAIL_SIZE[0] + 16.0)).max(1.0) as usize;
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                Grid::new("rom_browser").show(ui, |ui| {
+                    for (i, entry) in self.rom_browser.iter().enumerate() {
+                        let name = entry.path.file_stem().unwrap_or_default().to_string_lossy();
+                        ui.vertical(|ui| {
+                            match &entry.thumbnail {
+                                Some(Ok(texture)) => {
+                                    let button =
+                                        egui::ImageButton::new(texture.id(), THUMBNAIL_SIZE);
+                                    if ui.add(button).on_hover_text(&*name).clicked() {
+                                        picked = Some(entry.path.clone());
+                                    }
+                                }
+                                Some(Err(e)) => {
+                                    ui.add_sized(
+                                        THUMBNAIL_SIZE,
+                                        egui::Label::new(
+                                            RichText::new("No preview").color(Color32::RED),
+                                        ),
+                                    )
+                                    .on_hover_text(e);
+                                }
+                                None => {
+                                    ui.add_sized(THUMBNAIL_SIZE, egui::Spinner::new());
+                                }
+                            }
+                            ui.label(&*name);
+                        });
+                        if (i + 1) % columns == 0 {
+                            ui.end_row();
+                        }
+                    }
+                });
+            });
+        });
+
+        if choose_folder {
+            if let Some(path) = rfd::FileDialog::new().set_directory(&dir).pick_folder() {
+                self.rom_directory = Some(path);
+            }
+        }
+        if let Some(path) = picked {
+            if self.load_rom(emu, &path) {
+                emu.run_steps = false;
+                close = true;
+            }
+        }
+        if close {
+            self.show_rom_browser = false;
+        }
+    }
+
+    fn open_font_dialog(&mut self, emu: &mut Emu) {
+        if let Some(path) = rfd::FileDialog::new().pick_file() {
+            if let Err(e) = emu.load_font_file(&path) {
+                self.error_dialog = Some(format!("Failed to load {}: {}", path.display(), e));
+            }
+        }
+    }
+
+    fn build_and_run(&mut self, emu: &mut Emu) {
+        match assembler::assemble(&self.author_source) {
+            Ok(rom) => {
+                self.author_errors.clear();
+                // A failure is logged to the console
+                if emu.load_rom_bytes(rom).is_ok() {
+                    emu.run_steps = false;
+                }
+            }
+            Err(errors) => self.author_errors = errors,
+        }
+    }
+
+    fn author_panels(
+        &mut self,
+        ctx: &egui::Context,
+        emu: &mut Emu,
+        gfx_texture_id: egui::TextureId,
+    ) {
+        let mut build = {
+            let input = ctx.input();
+            input.modifiers.command && input.key_pressed(Key::B)
+        };
+
+        egui::SidePanel::left("author_editor")
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Build & Run").clicked() {
+                        build = true;
+                    }
+                    ui.label("Ctrl+B");
+                });
+                for error in &self.author_errors {
+                    ui.colored_label(Color32::RED, error.to_string());
+                }
+
+                ui.separator();
+
+                let highlighter = &self.highlighter;
+                let errors = &self.author_errors;
+                let mut layouter = |ui: &egui::Ui, source: &str, wrap_width: f32| {
+                    let mut job = highlighter.highlight(source, errors);
+                    job.wrap_width = wrap_width;
+                    ui.fonts().layout_job(job)
+                };
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.author_source)
+                            .code_editor()
+                            .desired_width(f32::INFINITY)
+                            .layouter(&mut layouter),
+                    );
+                });
+            });
+
+        egui::SidePanel::right("author_preview")
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.heading("Preview");
+                let width = ui.available_width();
+                ui.image(gfx_texture_id, vec2(width, width / 2.0));
+            });
+
+        if build {
+            self.build_and_run(emu);
         }
     }
 
     fn ui(&mut self, ctx: &egui::Context, emu: &mut Emu) {
+        // Nothing but the display, the windows come back as they were when it is turned off
+        if emu.presentation_mode {
+            return;
+        }
+
+        let gfx_size = [emu.cpu.gfx.width(), emu.cpu.gfx.height()];
+        let gfx_image = ColorImage {
+            size: gfx_size,
+            pixels: (0..gfx_size[1])
+                .flat_map(|y| (0..gfx_size[0]).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    let [r, g, b, a] = emu.pixel_color(x, y);
+                    Color32::from_rgba_unmultiplied(r, g, b, a)
+                })
+                .collect(),
+        };
+        let gfx_texture = self.gfx_texture.get_or_insert_with(|| {
+            ctx.load_texture("gfx", ColorImage::new(gfx_size, Color32::BLACK))
+        });
+        gfx_texture.set(gfx_image);
+        let gfx_texture_id = gfx_texture.id();
+
+        let mut open_rom = false;
+        let mut save_state = false;
+        let mut load_state = false;
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    open_rom = ui.button("Open ROM…").clicked();
+                    if ui.button("ROM Browser").clicked() {
+                        self.show_rom_browser = true;
+                        ui.close_menu();
+                    }
+                    save_state = ui.button("Save State…").clicked();
+                    load_state = ui.button("Load State…").clicked();
+                    ui.separator();
+                    self.quit_requested = ui.button("Quit").clicked();
+                    if open_rom || save_state || load_state || self.quit_requested {
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("Help", |ui| {
+                    if ui.button("About").clicked() {
+                        self.show_about = true;
+                        ui.close_menu();
+                    }
+                });
+            });
+        });
+        if save_state {
+            self.save_state_dialog(emu);
+        }
+        if self.show_rom_browser {
+            self.rom_browser(ctx, emu);
+        }
+        if load_state {
+            self.load_state_dialog(emu);
+        }
+
+        egui::Window::new("About")
+            .open(&mut self.show_about)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.heading(format!("cchipt {}", env!("CARGO_PKG_VERSION")));
+                ui.label("A CHIP-8 emulator and debugger");
+                ui.label(format!("Built {}", env!("CCHIPT_BUILD_DATE")));
+                ui.hyperlink(env!("CARGO_PKG_REPOSITORY"));
+            });
+
+        if let Some((shown_at, message)) = &self.toast {
+            if shown_at.elapsed() < TOAST_DURATION {
+                egui::Area::new("toast")
+                    .anchor(Align2::CENTER_BOTTOM, [0.0, -40.0])
+                    .show(ctx, |ui| {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| ui.label(message));
+                    });
+            } else {
+                self.toast = None;
+            }
+        }
+
+        let mut dismiss_error = false;
+        if let Some(error) = &self.error_dialog {
+            egui::Window::new("Error")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.colored_label(Color32::RED, error);
+                    dismiss_error = ui.button("OK").clicked();
+                });
+        }
+        if dismiss_error {
+            self.error_dialog = None;
+        }
+
+        if self.author_mode {
+            self.author_panels(ctx, emu, gfx_texture_id);
+        }
+
+        let mut save_config = false;
         egui::Window::new("Run Controls")
             .open(&mut self.show_run_controls)
             .anchor(Align2::CENTER_TOP, [0.0, 0.0])
             .show(ctx, |ui| {
+                // Started without a ROM, so there is nothing to run until one is loaded
+                if emu.rom.is_empty() {
+                    ui.colored_label(Color32::YELLOW, "No ROM loaded");
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.rom_path);
+                        if ui.button("Load ROM").clicked() {
+                            self.rom_error =
+                                emu.load_rom(&self.rom_path).err().map(|e| e.to_string());
+                        }
+                    });
+                    if let Some(error) = &self.rom_error {
+                        ui.colored_label(Color32::RED, error);
+                    }
+                    ui.separator();
+                }
+
+                if ui.button("Open ROM…").clicked() {
+                    open_rom = true;
+                }
+                if let Some(warning) = &emu.rom_warning {
+                    ui.colored_label(Color32::YELLOW, format!("Warning: {}", warning));
+                }
+
                 Grid::new("info").show(ui, |ui| {
                     ui.label("Status");
-                    if emu.run_steps {
+                    if let Some(CpuError::Exited(_)) = emu.cpu.halted {
+                        ui.colored_label(Color32::GRAY, "EXITED");
+                    } else if emu.cpu.halted.is_some() {
+                        ui.colored_label(Color32::RED, "HALTED");
+                    } else if emu.run_steps {
                         ui.colored_label(Color32::YELLOW, "PAUSED");
+                    } else if emu.boosted {
+                        ui.colored_label(Color32::LIGHT_BLUE, "RUNNING (FAST)");
                     } else {
                         ui.colored_label(Color32::GREEN, "RUNNING");
                     }
                     ui.end_row();
                     ui.label("Clock Rate");
                     ui.label(format!("{}", emu.clock_rate));
+                    ui.end_row();
+                    ui.label("Effective Clock Rate");
+                    ui.label(format!("{}", emu.effective_clock_rate));
+                    ui.end_row();
+                    ui.label("Cycles");
+                    ui.label(format!("{}", emu.total_cycles));
+                    ui.end_row();
+                    ui.label("Frames");
+                    ui.label(format!("{}", emu.total_frames));
+                    // To compare against the CRC32s listed in ROM databases
+                    if !emu.rom.is_empty() {
+                        ui.end_row();
+                        ui.label("ROM CRC32");
+                        ui.monospace(format!("{:08X}", emu.initial_rom_checksum));
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    let clock_rate = emu.clock_rate;
+                    // Rates outside the slider's range can still come from the command line
+                    let response = ui.add(
+                        egui::Slider::new(&mut emu.clock_rate, 60..=2000)
+                            .clamp_to_range(false)
+                            .suffix(" Hz"),
+                    );
+                    if ui.button("Reset to default").clicked() {
+                        emu.clock_rate = DEFAULT_CLOCK_RATE;
+                    }
+                    // Dragging changes the rate every frame, so a drag is logged once it ends
+                    if response.drag_started() {
+                        self.clock_rate_drag_start = Some(clock_rate);
+                    }
+                    if !response.dragged() {
+                        let old_rate = self.clock_rate_drag_start.take().unwrap_or(clock_rate);
+                        if old_rate != emu.clock_rate {
+                            emu.log_config_change(format!(
+                                "Clock rate changed: {} → {} Hz",
+                                old_rate, emu.clock_rate
+                            ));
+                        }
+                    }
+                });
+
+                if emu.exceeds_host_capacity() {
+                    ui.colored_label(
+                        Color32::YELLOW,
+                        format!(
+                            "Warning: Host may not sustain {} Hz; measured max is {} Hz",
+                            emu.clock_rate, emu.max_clock_rate
+                        ),
+                    );
+                }
+                if ui
+                    .checkbox(&mut emu.auto_lower_clock_rate, "Auto-lower clock rate")
+                    .changed()
+                {
+                    emu.log_config_change(format!(
+                        "Auto-lower clock rate changed: {}",
+                        emu.auto_lower_clock_rate
+                    ));
+                }
+
+                ui.horizontal(|ui| {
+                    let (icon, hover_text) = match emu.sound_muted {
+                        true => ("🔇", "Unmute"),
+                        false => ("🔊", "Mute"),
+                    };
+                    let mut changed = ui.button(icon).on_hover_text(hover_text).clicked();
+                    if changed {
+                        emu.sound_muted = !emu.sound_muted;
+                    }
+                    changed |= ui
+                        .add_enabled(
+                            !emu.sound_muted,
+                            egui::Slider::new(&mut emu.sound_volume, 0.0..=1.0).text("Volume"),
+                        )
+                        .changed();
+                    if changed {
+                        emu.sound.set_volume(emu.output_volume());
+                    }
                 });
 
+                let mode = emu.cpu.mode;
+                egui::ComboBox::from_label("Mode")
+                    .selected_text(mode.name())
+                    .show_ui(ui, |ui| {
+                        for new_mode in Mode::ALL {
+                            ui.selectable_value(&mut emu.cpu.mode, new_mode, new_mode.name());
+                        }
+                    });
+                if emu.cpu.mode != mode {
+                    // Plain CHIP-8 has no way back from high resolution mode or extra bitplanes
+                    emu.cpu.reset_display();
+                    emu.log_config_change(format!(
+                        "Mode changed: {} → {}",
+                        mode.name(),
+                        emu.cpu.mode.name()
+                    ));
+                }
+
                 ui.separator();
 
                 ui.horizontal(|ui| {
                     if ui.button("Run").clicked() {
-                        emu.run_steps = false;
+                        emu.resume();
                     }
                     if ui.button("Pause").clicked() {
                         emu.run_steps = true;
                     }
                     ui.separator();
+                    if ui
+                        .add_enabled(!emu.history.is_empty(), egui::Button::new("Step Back"))
+                        .clicked()
+                    {
+                        emu.step_back();
+                    }
                     if ui.button("Step").clicked() {
-                        emu.progress();
+                        if let Err(e) = emu.step() {
+                            self.error_dialog = Some(format!("CPU halted: {}", e));
+                        }
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut emu.step_size)
+                            .clamp_range(1..=10_000)
+                            .prefix("×"),
+                    )
+                    .on_hover_text("Instructions per step");
+                    if ui.button("Reset").clicked() {
+                        emu.reset();
+                    }
+                    if ui
+                        .add_enabled(emu.cpu.halted.is_some(), egui::Button::new("Clear Halt"))
+                        .clicked()
+                    {
+                        emu.cpu.clear_halt();
+                    }
+                    ui.separator();
+                    if ui.button("Screenshot").clicked() {
+                        match emu.save_screenshot() {
+                            Ok(path) => {
+                                self.toast =
+                                    Some((Instant::now(), format!("Screenshot saved: {}", path)));
+                            }
+                            Err(e) => eprintln!("Failed to save screenshot: {}", e),
+                        }
+                    }
+                    ui.separator();
+                    ui.checkbox(&mut self.author_mode, "Author Mode");
+                    if ui.button("Speed Challenge").clicked() {
+                        emu.start_speed_challenge(
+                            emu.clock_rate,
+                            self.speed_challenge_step_hz,
+                            self.speed_challenge_interval,
+                            self.speed_challenge_max_hz,
+                        );
+                        emu.log_config_change(format!(
+                            "Speed challenge started at {} Hz",
+                            emu.clock_rate
+                        ));
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut self.speed_challenge_step_hz)
+                            .clamp_range(1..=1000)
+                            .prefix("+")
+                            .suffix(" Hz"),
+                    )
+                    .on_hover_text("Clock rate added every step");
+                    ui.add(
+                        egui::DragValue::new(&mut self.speed_challenge_interval)
+                            .clamp_range(1..=60)
+                            .suffix(" s"),
+                    )
+                    .on_hover_text("Time between steps");
+                    ui.add(
+                        egui::DragValue::new(&mut self.speed_challenge_max_hz)
+                            .clamp_range(60..=emu.max_clock_rate.max(60))
+                            .prefix("≤ ")
+                            .suffix(" Hz"),
+                    )
+                    .on_hover_text("Clock rate the challenge ends at");
+                });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.state_path);
+                    if ui.button("Save State").clicked() {
+                        self.state_error = emu
+                            .save_state()
+                            .write(&self.state_path)
+                            .err()
+                            .map(|e| e.to_string());
+                    }
+                    if ui.button("Load State").clicked() {
+                        self.state_error = EmuState::read(&self.state_path)
+                            .and_then(|state| emu.load_state(state))
+                            .err()
+                            .map(|e| e.to_string());
+                    }
+                });
+                if let Some(error) = &self.state_error {
+                    ui.colored_label(Color32::RED, error);
+                }
+
+                ui.separator();
+
+                save_config = ui.button("Save Config").clicked();
+            });
+        if save_config {
+            self.save_config(emu);
+        }
+        if open_rom {
+            self.open_rom_dialog(emu);
+        }
+
+        egui::Window::new("Debug")
+            .open(&mut self.show_debug)
+            .show(ctx, |ui| {
+                if ui
+                    .checkbox(
+                        &mut emu.detect_self_modification,
+                        "Detect Self-Modification",
+                    )
+                    .changed()
+                {
+                    emu.log_config_change(format!(
+                        "Detect self-modification changed: {}",
+                        emu.detect_self_modification
+                    ));
+                }
+                ui.checkbox(&mut self.show_settings_history, "Show Settings History");
+                ui.checkbox(&mut self.show_console, "Show Console");
+                ui.checkbox(&mut self.show_quirks, "Show Quirks");
+                ui.checkbox(&mut self.show_key_mapping, "Show Key Mapping");
+                ui.checkbox(&mut self.show_trace, "Show Trace");
+                ui.checkbox(&mut self.show_profiler, "Show Profiler");
+                ui.checkbox(&mut self.show_sound, "Show Sound");
+                ui.checkbox(&mut self.show_compatibility, "Show Compatibility");
+                ui.checkbox(&mut self.show_rom_diff, "Show ROM Diff");
+                ui.checkbox(&mut self.show_disassembly, "Show Disassembly");
+                ui.label(format!("ROM Checksum: {:08x}", emu.initial_rom_checksum));
+
+                egui::CollapsingHeader::new("Breakpoints").show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.breakpoint_input);
+                        let address = u16::from_str_radix(self.breakpoint_input.trim(), 16).ok();
+                        if ui.button("Add").clicked() {
+                            if let Some(address) = address {
+                                if !emu.breakpoints.contains(&address) {
+                                    emu.breakpoints.push(address);
+                                }
+                            }
+                        }
+                        if ui.button("Remove").clicked() {
+                            emu.breakpoints.retain(|a| Some(*a) != address);
+                        }
+                    });
+                    for address in &emu.breakpoints {
+                        ui.monospace(format!("{:04X}", address));
+                    }
+                });
+
+                egui::CollapsingHeader::new("Watchpoints").show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.watchpoint_input);
+                        egui::ComboBox::from_id_source("watch_kind")
+                            .selected_text(self.watchpoint_kind.name())
+                            .show_ui(ui, |ui| {
+                                for kind in WatchKind::ALL {
+                                    ui.selectable_value(
+                                        &mut self.watchpoint_kind,
+                                        kind,
+                                        kind.name(),
+                                    );
+                                }
+                            });
+                        let address = u16::from_str_radix(self.watchpoint_input.trim(), 16).ok();
+                        if ui.button("Add").clicked() {
+                            if let Some(address) = address {
+                                emu.memory_watchpoints.retain(|(a, _)| *a != address);
+                                emu.memory_watchpoints.push((address, self.watchpoint_kind));
+                            }
+                        }
+                    });
+                    let mut removed = None;
+                    for (i, (address, kind)) in emu.memory_watchpoints.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.monospace(format!("{:04X} {}", address, kind.name()));
+                            if ui.small_button("Remove").clicked() {
+                                removed = Some(i);
+                            }
+                        });
+                    }
+                    if let Some(i) = removed {
+                        emu.memory_watchpoints.remove(i);
+                    }
+                });
+
+                egui::CollapsingHeader::new("Conditions").show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.condition_input)
+                                .hint_text("V3 == 42"),
+                        );
+                        if ui.button("Add").clicked() {
+                            match self.condition_input.parse::<ConditionalBreakpoint>() {
+                                Ok(condition) => {
+                                    self.condition_error = None;
+                                    if !emu.conditional_breakpoints.contains(&condition) {
+                                        emu.conditional_breakpoints.push(condition);
+                                    }
+                                }
+                                Err(e) => self.condition_error = Some(e),
+                            }
+                        }
+                    });
+                    if let Some(error) = &self.condition_error {
+                        ui.colored_label(Color32::RED, error);
+                    }
+                    let mut removed = None;
+                    for (i, condition) in emu.conditional_breakpoints.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.monospace(condition.to_string());
+                            if ui.small_button("Remove").clicked() {
+                                removed = Some(i);
+                            }
+                        });
+                    }
+                    if let Some(i) = removed {
+                        emu.conditional_breakpoints.remove(i);
+                    }
+                });
+
+                ui.separator();
+
+                if ui.button("Clear Events").clicked() {
+                    emu.events.clear();
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for event in &emu.events {
+                        match event {
+                            EmuEvent::RomModified { address } => {
+                                ui.colored_label(
+                                    Color32::YELLOW,
+                                    format!("ROM modified at {:04X}", address),
+                                );
+                            }
+                            EmuEvent::BreakpointHit { address } => {
+                                ui.colored_label(
+                                    Color32::LIGHT_BLUE,
+                                    format!("Breakpoint hit at {:04X}", address),
+                                );
+                            }
+                            EmuEvent::WatchpointHit {
+                                address,
+                                pc,
+                                opcode,
+                            } => {
+                                ui.colored_label(
+                                    Color32::LIGHT_BLUE,
+                                    format!(
+                                        "Watchpoint {:04X} hit by {:04X} ({}) at {:04X}",
+                                        address,
+                                        opcode,
+                                        Chip8::decode_instruction(opcode),
+                                        pc
+                                    ),
+                                );
+                            }
+                            EmuEvent::ConditionMet { condition, pc } => {
+                                ui.colored_label(
+                                    Color32::LIGHT_BLUE,
+                                    format!("Condition {} met at {:04X}", condition, pc),
+                                );
+                            }
+                            EmuEvent::Halted { error } => {
+                                ui.colored_label(Color32::RED, format!("Halted: {}", error));
+                            }
+                            EmuEvent::SpeedChallengePaused { clock_rate } => {
+                                ui.colored_label(
+                                    Color32::YELLOW,
+                                    format!(
+                                        "Speed challenge paused at {} Hz, it carries on when the program runs again",
+                                        clock_rate
+                                    ),
+                                );
+                            }
+                        }
+                    }
+                });
+            });
+
+        egui::Window::new("Performance")
+            .open(&mut self.show_performance)
+            .show(ctx, |ui| {
+                Grid::new("performance").show(ui, |ui| {
+                    ui.label("Average Frame Time");
+                    ui.label(format!("{:.2} ms", emu.average_frame_time()));
+                    ui.end_row();
+                    ui.label("99th Percentile");
+                    ui.label(format!("{} ms", emu.frame_time_percentile(99.0)));
+                    if let Some(challenge) = &emu.speed_challenge {
+                        ui.end_row();
+                        ui.label("Speed Challenge");
+                        ui.label(format!("{}/{}", challenge.stage, challenge.total_stages));
+                    }
+                });
+
+                ui.separator();
+
+                if ui.button("Export Timing Log").clicked() {
+                    if let Err(e) = emu.export_timing_log("timing_log.csv") {
+                        eprintln!("Failed to export timing log: {}", e);
+                    }
+                }
+            });
+
+        egui::Window::new("ROM Info")
+            .open(&mut self.show_rom_info)
+            .show(ctx, |ui| match &emu.rom_info {
+                Some(info) => match &info.metadata {
+                    Some(metadata) => {
+                        Grid::new("rom_info").show(ui, |ui| {
+                            ui.label("Name");
+                            ui.label(&metadata.name);
+                            ui.end_row();
+                            ui.label("Author");
+                            ui.label(&metadata.author);
+                            ui.end_row();
+                            ui.label("Year");
+                            ui.label(format!("{}", metadata.year));
+                            ui.end_row();
+                            ui.label("Quirks");
+                            ui.label(format!("{:?}", metadata.quirks_preset));
+                            ui.end_row();
+                            ui.label("Difficulty");
+                            ui.label(format!("{}", metadata.difficulty));
+                            ui.end_row();
+                        });
+                        ui.separator();
+                        ui.label(&metadata.description);
+                    }
+                    None => {
+                        ui.colored_label(Color32::YELLOW, "Unknown ROM");
+                        if !info.header.is_empty() {
+                            Grid::new("rom_header").show(ui, |ui| {
+                                for (label, value) in [
+                                    ("Title", &info.header.title),
+                                    ("Author", &info.header.author),
+                                ] {
+                                    if let Some(value) = value {
+                                        ui.label(label);
+                                        ui.label(value);
+                                        ui.end_row();
+                                    }
+                                }
+                            });
+                            if let Some(description) = &info.header.description {
+                                ui.label(description);
+                            }
+                            ui.separator();
+                        }
+                        ui.label(format!("SHA-256: {}", info.sha256));
+                        ui.hyperlink_to("Submit Metadata", info.submit_url());
+                    }
+                },
+                None => {
+                    ui.label("No ROM loaded");
+                }
+            });
+
+        let mut open_font = false;
+        egui::Window::new("Display")
+            .open(&mut self.show_display)
+            .show(ctx, |ui| {
+                let frame_skip = emu.frame_skip;
+                egui::ComboBox::from_label("Frame Skip")
+                    .selected_text(format!("{}x", emu.frame_skip))
+                    .show_ui(ui, |ui| {
+                        for skip in [1, 2, 4, 8] {
+                            ui.selectable_value(&mut emu.frame_skip, skip, format!("{}x", skip));
+                        }
+                    });
+                if emu.frame_skip != frame_skip {
+                    emu.log_config_change(format!(
+                        "Frame skip changed: {}x → {}x",
+                        frame_skip, emu.frame_skip
+                    ));
+                }
+
+                let ghost_frames = emu.ghost_frames;
+                let response = ui.add(
+                    egui::Slider::new(&mut emu.ghost_frames, 0..=MAX_GHOST_FRAMES)
+                        .text("Ghost Trail"),
+                );
+                if response.drag_started() {
+                    self.ghost_frames_drag_start = Some(ghost_frames);
+                }
+                if !response.dragged() {
+                    let from = self.ghost_frames_drag_start.take().unwrap_or(ghost_frames);
+                    if from != emu.ghost_frames {
+                        emu.log_config_change(format!(
+                            "Ghost trail changed: {} → {} frames",
+                            from, emu.ghost_frames
+                        ));
+                    }
+                }
+
+                ui.separator();
+
+                // Only XO-CHIP can set pixels in more than one bitplane
+                let names: &[&str] = match emu.cpu.mode {
+                    Mode::XoChip => &["Off", "Plane 1", "Plane 2", "Both Planes"],
+                    _ => &["Off", "On"],
+                };
+                let mut palette_changed = false;
+                Grid::new("palette").show(ui, |ui| {
+                    for (name, color) in names.iter().zip(emu.palette.iter_mut()) {
+                        ui.label(*name);
+                        if ui.color_edit_button_srgba_unmultiplied(color).changed() {
+                            palette_changed = true;
+                        }
+                        ui.end_row();
+                    }
+                });
+                if ui.button("Reset Palette").clicked() {
+                    emu.palette = DEFAULT_PALETTE;
+                    emu.log_config_change("Palette reset");
+                    palette_changed = true;
+                }
+                // The palette is only applied when the display is drawn
+                if palette_changed {
+                    emu.cpu.gfx_dirty = true;
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.checkbox(&mut emu.show_grid, "Show Grid").changed() {
+                        emu.log_config_change(format!("Show grid changed: {}", emu.show_grid));
+                        emu.cpu.gfx_dirty = true;
+                    }
+                    if ui
+                        .color_edit_button_srgba_unmultiplied(&mut emu.grid_color)
+                        .changed()
+                    {
+                        emu.cpu.gfx_dirty = true;
+                    }
+                });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Font");
+                    for (name, font) in [
+                        ("Default", CHARACTER_SPRITES),
+                        ("Thin", THIN_CHARACTER_SPRITES),
+                    ] {
+                        if ui.button(name).clicked() {
+                            emu.cpu.load_font(&font);
+                            emu.log_config_change(format!("Font changed: {}", name));
+                        }
+                    }
+                    open_font = ui.button("Load Font…").clicked();
+                });
+            });
+        if open_font {
+            self.open_font_dialog(emu);
+        }
+
+        if let (Some(slot), Some(key)) = (self.key_binding, self.captured_key.take()) {
+            // Escape cancels rebinding
+            if key != VirtualKeyCode::Escape && emu.key_map[slot] != key {
+                emu.log_config_change(format!(
+                    "Key {:X} changed: {:?} → {:?}",
+                    slot, emu.key_map[slot], key
+                ));
+                emu.key_map[slot] = key;
+            }
+            self.key_binding = None;
+        }
+        if let (true, Some(key)) = (self.binding_step_key, self.captured_key.take()) {
+            if key != VirtualKeyCode::Escape && emu.step_key != key {
+                emu.log_config_change(format!("Step key changed: {:?} → {:?}", emu.step_key, key));
+                emu.step_key = key;
+            }
+            self.binding_step_key = false;
+        }
+        if let (true, Some(key)) = (self.binding_boost_key, self.captured_key.take()) {
+            if key != VirtualKeyCode::Escape && emu.boost_key != key {
+                emu.log_config_change(format!(
+                    "Boost key changed: {:?} → {:?}",
+                    emu.boost_key, key
+                ));
+                emu.boost_key = key;
+            }
+            self.binding_boost_key = false;
+        }
+
+        egui::Window::new("Key Mapping")
+            .open(&mut self.show_key_mapping)
+            .show(ctx, |ui| {
+                ui.label("Click a key, then press the host key to bind to it");
+                // Same layout as the COSMAC VIP keypad
+                Grid::new("key_mapping").show(ui, |ui| {
+                    for row in [
+                        [0x1, 0x2, 0x3, 0xC],
+                        [0x4, 0x5, 0x6, 0xD],
+                        [0x7, 0x8, 0x9, 0xE],
+                        [0xA, 0x0, 0xB, 0xF],
+                    ] {
+                        for key in row {
+                            let binding = self.key_binding == Some(key);
+                            let text = match binding {
+                                true => format!("{:X}: …", key),
+                                false => format!("{:X}: {:?}", key, emu.key_map[key]),
+                            };
+                            if ui.selectable_label(binding, text).clicked() {
+                                self.key_binding = (!binding).then_some(key);
+                                self.binding_step_key = false;
+                                self.binding_boost_key = false;
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Step while paused");
+                    let text = match self.binding_step_key {
+                        true => String::from("…"),
+                        false => format!("{:?}", emu.step_key),
+                    };
+                    if ui.selectable_label(self.binding_step_key, text).clicked() {
+                        self.binding_step_key = !self.binding_step_key;
+                        self.key_binding = None;
+                        self.binding_boost_key = false;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label(format!("Hold to run {}x faster", BOOST_FACTOR));
+                    let text = match self.binding_boost_key {
+                        true => String::from("…"),
+                        false => format!("{:?}", emu.boost_key),
+                    };
+                    if ui.selectable_label(self.binding_boost_key, text).clicked() {
+                        self.binding_boost_key = !self.binding_boost_key;
+                        self.key_binding = None;
+                        self.binding_step_key = false;
+                    }
+                });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    for (name, key_map) in
+                        [("Default", DEFAULT_KEY_MAP), ("Hex Pad", HEX_PAD_KEY_MAP)]
+                    {
+                        if ui.button(name).clicked() && emu.key_map != key_map {
+                            emu.key_map = key_map;
+                            emu.log_config_change(format!("Key mapping changed: {}", name));
+                        }
+                    }
+                });
+            });
+
+        egui::Window::new("Quirks")
+            .open(&mut self.show_quirks)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for preset in QuirksPreset::ALL {
+                        if ui.button(preset.name()).clicked() {
+                            emu.cpu.quirks = Quirks::from(preset);
+                            emu.log_config_change(format!(
+                                "Quirks preset applied: {}",
+                                preset.name()
+                            ));
+                        }
+                    }
+                });
+
+                ui.separator();
+
+                let quirks = &mut emu.cpu.quirks;
+                let toggles = [
+                    (&mut quirks.vf_reset, "VF Reset", "8xy1/2/3 reset VF to 0"),
+                    (
+                        &mut quirks.memory_increment,
+                        "Memory Increment",
+                        "Fx55/Fx65 increment I by x + 1",
+                    ),
+                    (
+                        &mut quirks.shift_uses_vy,
+                        "Shift Uses Vy",
+                        "8xy6/8xyE shift Vy and store the result in Vx",
+                    ),
+                    (
+                        &mut quirks.jump_uses_vx,
+                        "Jump Uses Vx",
+                        "Bnnn jumps to xnn + Vx instead of nnn + V0",
+                    ),
+                    (
+                        &mut quirks.clip_sprites,
+                        "Clip Sprites",
+                        "Sprites are clipped at the screen edges instead of wrapping",
+                    ),
+                    (
+                        &mut quirks.display_wait,
+                        "Display Wait",
+                        "DRW waits for the next frame, limiting drawing to 60 sprites per second",
+                    ),
+                    (
+                        &mut quirks.vf_keeps_result,
+                        "VF Keeps Result",
+                        "8Fy4/5/7 store the result in VF instead of the carry or borrow flag",
+                    ),
+                ];
+                let mut change = None;
+                for (value, label, description) in toggles {
+                    if ui
+                        .checkbox(value, label)
+                        .on_hover_text(description)
+                        .changed()
+                    {
+                        change = Some(format!("{} changed: {}", label, value));
+                    }
+                }
+                if let Some(change) = change {
+                    emu.log_config_change(change);
+                }
+            });
+
+        egui::Window::new("Compatibility")
+            .open(&mut self.show_compatibility)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        Grid::new("compatibility").striped(true).show(ui, |ui| {
+                            ui.label("Opcode");
+                            for preset in QuirksPreset::ALL {
+                                ui.label(preset.name());
+                            }
+                            ui.end_row();
+
+                            for (i, row) in self.compatibility.rows.iter().enumerate() {
+                                ui.monospace(row.opcode);
+                                for (j, cell) in row.cells.iter().enumerate() {
+                                    let (text, color) = match cell.compatibility {
+                                        Compatibility::Supported => {
+                                            ("Supported", Color32::DARK_GREEN)
+                                        }
+                                        Compatibility::UnsupportedIgnored => {
+                                            ("Ignored", Color32::DARK_GRAY)
+                                        }
+                                        Compatibility::UnsupportedCrash => {
+                                            ("Crash", Color32::DARK_RED)
+                                        }
+                                        Compatibility::BehaviorDiffers => {
+                                            ("Differs", Color32::from_rgb(0x80, 0x60, 0x00))
+                                        }
+                                    };
+                                    let button = egui::Button::new(
+                                        RichText::new(text).color(Color32::WHITE),
+                                    )
+                                    .fill(color);
+                                    if ui.add(button).clicked() {
+                                        self.compatibility_selection = Some((i, j));
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+
+                if let Some((i, j)) = self.compatibility_selection {
+                    let row = &self.compatibility.rows[i];
+                    ui.separator();
+                    ui.heading(format!("{} - {}", row.opcode, row.mnemonic));
+                    ui.label(row.description);
+                    ui.label(format!(
+                        "{}: {}",
+                        QuirksPreset::ALL[j].name(),
+                        row.cells[j].note
+                    ));
+                }
+            });
+
+        egui::Window::new("ROM Diff")
+            .open(&mut self.show_rom_diff)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.diff_path);
+                    if ui.button("Open Second ROM").clicked() {
+                        match std::fs::read(&self.diff_path) {
+                            Ok(rom_b) => {
+                                self.rom_diff = diff_roms(&emu.rom, &rom_b);
+                                self.diff_error = None;
+                            }
+                            Err(e) => self.diff_error = Some(e.to_string()),
+                        }
+                    }
+                });
+                if let Some(error) = &self.diff_error {
+                    ui.colored_label(Color32::RED, error);
+                }
+
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    Grid::new("rom_diff").striped(true).show(ui, |ui| {
+                        for entry in &self.rom_diff {
+                            let color = match entry.kind() {
+                                DiffKind::Same => ui.visuals().text_color(),
+                                DiffKind::Changed => Color32::RED,
+                                DiffKind::Added => Color32::GREEN,
+                                DiffKind::Removed => Color32::GRAY,
+                            };
+                            let column = |opcode: Option<u16>, mnemonic: &str| match opcode {
+                                Some(opcode) => format!("{:04X}  {}", opcode, mnemonic),
+                                None => String::new(),
+                            };
+
+                            ui.monospace(format!("{:04X}", entry.address));
+                            ui.colored_label(color, column(entry.opcode_a, &entry.mnemonic_a));
+                            ui.colored_label(color, column(entry.opcode_b, &entry.mnemonic_b));
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        egui::Window::new("Settings History")
+            .open(&mut self.show_settings_history)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (_, entry) in emu.config_changes.iter().rev() {
+                        ui.label(entry);
+                    }
+                });
+            });
+
+        egui::Window::new("Console")
+            .open(&mut self.show_console)
+            .show(ctx, |ui| {
+                if ui.button("Clear").clicked() {
+                    emu.console.clear();
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom()
+                    .show(ui, |ui| {
+                        for line in &emu.console.lines {
+                            ui.monospace(line);
+                        }
+                    });
+            });
+
+        egui::Window::new("Sound")
+            .open(&mut self.show_sound)
+            .show(ctx, |ui| {
+                ui.label(format!("Sound timer: {}", emu.cpu.sound_timer));
+
+                // XO-CHIP ROMs play their audio pattern, everything else the plain tone
+                let pattern = (emu.cpu.mode == Mode::XoChip).then_some(AudioPattern {
+                    buffer: emu.cpu.audio_buffer,
+                    pitch: emu.cpu.audio_pitch,
+                });
+                // Starts a little further along every frame, so the wave moves like on a scope
+                let start = emu.total_frames as f64 / REFRESH_RATE as f64;
+                let active = emu.cpu.sound_timer > 0;
+                let values = (0..=WAVEFORM_POINTS).map(|i| {
+                    let t = WAVEFORM_DURATION * i as f64 / WAVEFORM_POINTS as f64;
+                    let y = match (active, pattern) {
+                        (false, _) => 0.0,
+                        (true, Some(pattern)) => {
+                            let sample = (start + t) * pattern.playback_rate() as f64;
+                            if pattern.sample(sample as usize) {
+                                1.0
+                            } else {
+                                -1.0
+                            }
+                        }
+                        (true, None) => (TAU * TONE_FREQUENCY as f64 * (start + t)).sin(),
+                    };
+                    Value::new(t * 1000.0, y)
+                });
+                Plot::new("waveform")
+                    .height(100.0)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .include_y(-1.0)
+                    .include_y(1.0)
+                    .show(ui, |plot_ui| {
+                        plot_ui.line(Line::new(Values::from_values_iter(values)).color(
+                            if active {
+                                Color32::GREEN
+                            } else {
+                                Color32::GRAY
+                            },
+                        ));
+                    });
+            });
+
+        egui::Window::new("Profiler")
+            .open(&mut self.show_profiler)
+            .show(ctx, |ui| {
+                let mut profile: Vec<(u16, u64)> =
+                    emu.cpu.profile.iter().map(|(&k, &v)| (k, v)).collect();
+                profile.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} instructions",
+                        profile.iter().map(|(_, count)| count).sum::<u64>()
+                    ));
+                    if ui.button("Clear").clicked() {
+                        emu.reset_profile();
+                    }
+                });
+
+                ui.separator();
+
+                // Most executed first, from the top of the chart down
+                let bars = profile
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(category, count))| {
+                        Bar::new(-(i as f64), count as f64).name(format!(
+                            "{} {}",
+                            Chip8::category_name(category),
+                            Chip8::decode_instruction(&category)
+                                .split_whitespace()
+                                .next()
+                                .unwrap_or("SYS")
+                        ))
+                    })
+                    .collect();
+                Plot::new("profiler")
+                    .height(200.0)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .show_x(false)
+                    .show(ui, |plot_ui| {
+                        plot_ui.bar_chart(BarChart::new(bars).horizontal());
+                    });
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    Grid::new("profile").striped(true).show(ui, |ui| {
+                        for (category, count) in profile {
+                            ui.monospace(Chip8::category_name(category));
+                            ui.label(format!("{}", count));
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        egui::Window::new("Trace")
+            .open(&mut self.show_trace)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut emu.trace_enabled, "Enabled");
+                    ui.label("Depth");
+                    ui.add(egui::DragValue::new(&mut emu.trace_depth).clamp_range(1..=65536));
+                    if ui.button("Clear").clicked() {
+                        emu.trace_log.clear();
+                    }
+                    if ui.button("Dump to file").clicked() {
+                        if let Err(e) = emu.export_trace_log("trace_log.csv") {
+                            eprintln!("Failed to export trace log: {}", e);
+                        }
                     }
                 });
+
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    Grid::new("trace").striped(true).show(ui, |ui| {
+                        for entry in emu.trace_log.iter().rev() {
+                            ui.monospace(format!("{:04x}", entry.pc));
+                            ui.monospace(format!("{:04x}", entry.opcode));
+                            ui.monospace(&entry.mnemonic);
+                            let registers = entry
+                                .registers
+                                .iter()
+                                .map(|v| format!("{:02x}", v))
+                                .collect::<Vec<_>>()
+                                .join(" ");
+                            ui.monospace(registers);
+                            ui.end_row();
+                        }
+                    });
+                });
             });
 
         egui::Window::new("CPU State")
             .open(&mut self.show_cpu_state)
             .anchor(Align2::LEFT_CENTER, [0.0, 0.0])
             .show(ctx, |ui| {
+                if let Some(error) = &emu.cpu.halted {
+                    ui.colored_label(Color32::RED, format!("HALTED: {}", error));
+                    ui.separator();
+                }
                 egui::Grid::new("register_grid").show(ui, |ui| {
                     ui.label("Program Counter");
                     ui.label(format!("{:04x}", emu.cpu.pc));
@@ -117,29 +1800,254 @@ impl Gui {
                     ui.end_row();
 
                     ui.label("Stack");
-                    egui::Grid::new("v_register").striped(true).show(ui, |ui| {
-                        for (i, v) in emu.cpu.stack.into_iter().enumerate() {
-                            ui.label(format!("0x{:01X}", i));
-                            ui.label(format!("{:04X}", v));
-                            if i % 2 == 1 {
-                                ui.end_row();
-                            }
+                    egui::Grid::new("stack").striped(true).show(ui, |ui| {
+                        // Each entry is the address of the CALL that pushed it
+                        for (i, address) in emu.cpu.stack.into_iter().enumerate() {
+                            let mnemonic = emu
+                                .cpu
+                                .memory
+                                .get(address as usize..address as usize + 2)
+                                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+                                .map(|opcode| Chip8::decode_instruction(&opcode))
+                                .unwrap_or_default();
+                            let color = match i < emu.cpu.sp as usize {
+                                true => ui.visuals().text_color(),
+                                false => ui.visuals().weak_text_color(),
+                            };
+                            ui.colored_label(color, format!("0x{:01X}", i));
+                            ui.colored_label(color, format!("{:04X}", address));
+                            ui.colored_label(color, mnemonic);
+                            ui.end_row();
                         }
                     });
                     ui.end_row();
                 });
+
+                ui.separator();
+
+                // Flat for a tight loop, a sawtooth for calls and returns
+                ui.label("PC History");
+                let values = emu
+                    .pc_history
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(age, &pc)| Value::new(-(age as f64), pc as f64));
+                Plot::new("pc_history")
+                    .height(80.0)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .include_x(-(PC_HISTORY_LEN as f64 - 1.0))
+                    .include_x(0.0)
+                    .x_axis_formatter(|steps, _| format!("{:.0}", steps))
+                    .y_axis_formatter(|address, _| format!("{:03X}", address.max(0.0) as u16))
+                    .label_formatter(|_, value| {
+                        format!("{:.0} steps ago\n{:03X}", -value.x, value.y.max(0.0) as u16)
+                    })
+                    .show(ui, |plot_ui| {
+                        plot_ui.line(Line::new(Values::from_values_iter(values)));
+                    });
+            });
+
+        egui::Window::new("Disassembly")
+            .open(&mut self.show_disassembly)
+            .show(ctx, |ui| {
+                // Only follow pc when it moves, so the view can still be scrolled while paused
+                let follow_pc = self.disassembly_pc != Some(emu.cpu.pc);
+                self.disassembly_pc = Some(emu.cpu.pc);
+
+                ui.horizontal(|ui| {
+                    ui.label("Click an address to toggle a breakpoint, right-click to run to it");
+                    if ui.button("Save listing").clicked() {
+                        if let Err(e) = emu.save_trace("disassembly.txt") {
+                            eprintln!("Failed to save disassembly: {}", e);
+                        }
+                    }
+                });
+                ui.separator();
+
+                let mut toggled_breakpoint = None;
+                let mut run_to = None;
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    Grid::new("disassembly").striped(true).show(ui, |ui| {
+                        let instructions = emu.rom.len().div_ceil(2);
+                        for (address, opcode, mnemonic) in
+                            emu.cpu.iter_instructions(0x200).take(instructions)
+                        {
+                            let color = if address == emu.cpu.pc {
+                                Color32::GREEN
+                            } else {
+                                ui.visuals().text_color()
+                            };
+
+                            let is_breakpoint = emu.breakpoints.contains(&address);
+                            let response = ui.selectable_label(
+                                is_breakpoint,
+                                RichText::new(format!("{:04X}", address))
+                                    .monospace()
+                                    .color(color),
+                            );
+                            if response.clicked() {
+                                toggled_breakpoint = Some(address);
+                            }
+                            let response = response.context_menu(|ui| {
+                                if ui.button("Run to here").clicked() {
+                                    run_to = Some(address);
+                                    ui.close_menu();
+                                }
+                            });
+                            if address == emu.cpu.pc && follow_pc {
+                                response.scroll_to_me(Some(Align::Center));
+                            }
+                            ui.label(
+                                RichText::new(format!("{:04x}", opcode))
+                                    .monospace()
+                                    .color(color),
+                            );
+                            ui.label(RichText::new(mnemonic).monospace().color(color));
+                            ui.end_row();
+                        }
+                    });
+                });
+                if let Some(address) = toggled_breakpoint {
+                    emu.toggle_breakpoint(address);
+                }
+                if let Some(address) = run_to {
+                    emu.run_to(address);
+                }
             });
 
         egui::Window::new("Memory")
             .anchor(Align2::RIGHT_TOP, [-2.0, 0.0])
             .open(&mut self.show_memory)
             .show(ctx, |ui| {
+                if ui.selectable_label(self.memory_edit_mode, "Edit").clicked() {
+                    self.memory_edit_mode = !self.memory_edit_mode;
+                    self.memory_edit = None;
+                }
+
+                ui.horizontal(|ui| {
+                    ui.colored_label(Color32::LIGHT_BLUE, "Font");
+                    ui.colored_label(Color32::GRAY, "Reserved");
+                    ui.colored_label(Color32::GREEN, "ROM");
+                    ui.colored_label(Color32::LIGHT_RED, "Modified ROM");
+                    ui.colored_label(Color32::WHITE, "RAM");
+                    ui.colored_label(Color32::YELLOW, "Written");
+                });
+
+                ui.horizontal(|ui| {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.memory_search)
+                            .hint_text("A2 00 D0 15")
+                            .desired_width(120.0),
+                    );
+                    // A new pattern is searched for from the top
+                    if response.changed() {
+                        self.memory_match = None;
+                    }
+                    let submitted = response.lost_focus() && ui.input().key_pressed(Key::Enter);
+                    if ui.button("Find Next").clicked() || submitted {
+                        let pattern = self
+                            .memory_search
+                            .split_whitespace()
+                            .map(|byte| u8::from_str_radix(byte, 16))
+                            .collect::<Result<Vec<_>, _>>();
+                        // Carries on after the last hit, so repeated searches go through every match
+                        let from = self.memory_match.map_or(0, |(address, _)| address + 1);
+                        match pattern {
+                            Ok(pattern) if pattern.is_empty() => {
+                                self.memory_match = None;
+                                self.memory_search_error = None;
+                            }
+                            Ok(pattern) => {
+                                match Chip8::search_memory(&emu.cpu.memory, &pattern, from) {
+                                    Some(address) => {
+                                        self.memory_match = Some((address, pattern.len()));
+                                        self.memory_search_error = None;
+                                        self.scroll_to_match = true;
+                                    }
+                                    None => {
+                                        self.memory_match = None;
+                                        self.memory_search_error = Some(String::from("Not found"));
+                                    }
+                                }
+                            }
+                            Err(_) => {
+                                self.memory_search_error =
+                                    Some(String::from("Enter hex bytes separated by spaces"));
+                            }
+                        }
+                    }
+                    if let Some(error) = &self.memory_search_error {
+                        ui.colored_label(Color32::RED, error);
+                    }
+                });
+
+                ui.separator();
+
                 egui::ScrollArea::vertical().show(ui, |ui| {
                     egui::Grid::new("memory_view").striped(true).show(ui, |ui| {
-                        for (row, chunk) in emu.cpu.memory.chunks(8).enumerate() {
-                            ui.label(format!("{:04X}", row * 8));
-                            for byte in chunk {
-                                ui.label(format!("{:02x}", byte));
+                        for row in 0..(emu.cpu.memory.len() / 8) {
+                            let response = ui.label(format!("{:04X}", row * 8));
+                            if let Some((match_address, _)) = self.memory_match {
+                                if self.scroll_to_match && match_address as usize / 8 == row {
+                                    response.scroll_to_me(Some(Align::Center));
+                                    self.scroll_to_match = false;
+                                }
+                            }
+                            for address in (row * 8)..(row * 8 + 8) {
+                                let byte = emu.cpu.memory[address];
+                                let recently_written = matches!(
+                                    &emu.last_memory_write,
+                                    Some((range, cycle)) if range.contains(&(address as u16))
+                                        && emu.total_cycles - cycle < WRITE_HIGHLIGHT_CYCLES
+                                );
+                                let rom_byte =
+                                    address.checked_sub(0x200).and_then(|i| emu.rom.get(i));
+                                let color = match (address, rom_byte) {
+                                    _ if recently_written => Color32::YELLOW,
+                                    // ROM bytes that no longer match the loaded file
+                                    (_, Some(rom_byte)) if *rom_byte != byte => Color32::LIGHT_RED,
+                                    (_, Some(_)) => Color32::GREEN,
+                                    (0x000..=0x04F, _) => Color32::LIGHT_BLUE,
+                                    (0x050..=0x1FF, _) => Color32::GRAY,
+                                    _ => Color32::WHITE,
+                                };
+
+                                if !self.memory_edit_mode {
+                                    let mut text =
+                                        RichText::new(format!("{:02x}", byte)).color(color);
+                                    if matches!(
+                                        self.memory_match,
+                                        Some((start, len)) if (start as usize..start as usize + len)
+                                            .contains(&address)
+                                    ) {
+                                        text = text.background_color(Color32::DARK_BLUE);
+                                    }
+                                    ui.label(text);
+                                    continue;
+                                }
+
+                                let mut text = match &self.memory_edit {
+                                    Some((edit_address, text)) if *edit_address == address => {
+                                        text.clone()
+                                    }
+                                    _ => format!("{:02x}", byte),
+                                };
+                                let response = ui.add(
+                                    egui::TextEdit::singleline(&mut text)
+                                        .desired_width(16.0)
+                                        .text_color(color),
+                                );
+                                // Enter also makes a single line edit lose focus
+                                if response.lost_focus() {
+                                    if let Ok(value) = u8::from_str_radix(text.trim(), 16) {
+                                        emu.cpu.memory[address] = value;
+                                    }
+                                    self.memory_edit = None;
+                                } else if response.has_focus() {
+                                    self.memory_edit = Some((address, text));
+                                }
                             }
                             ui.end_row();
                         }
@@ -151,16 +2059,42 @@ impl Gui {
             .anchor(Align2::RIGHT_BOTTOM, [0.0, 0.0])
             .open(&mut self.show_gfx)
             .show(ctx, |ui| {
-                ui.vertical(|ui| {
-                    for v in emu.cpu.gfx.chunks(64) {
-                        let contents = v
-                            .iter()
-                            .map(|b| if *b { "*" } else { "  " })
-                            .collect::<Vec<_>>()
-                            .join("");
-                        ui.label(contents);
+                ui.add(egui::Slider::new(&mut self.gfx_zoom, 1.0..=8.0).text("Zoom"));
+                let zoom = self.gfx_zoom;
+
+                let size = vec2(gfx_size[0] as f32 * zoom, gfx_size[1] as f32 * zoom);
+                let response = ui.image(gfx_texture_id, size);
+                let rect = response.rect;
+
+                match response.hover_pos() {
+                    Some(pos) => {
+                        let col = (((pos.x - rect.min.x) / zoom) as usize).min(gfx_size[0] - 1);
+                        let row = (((pos.y - rect.min.y) / zoom) as usize).min(gfx_size[1] - 1);
+
+                        let stroke = Stroke::new(1.0, Color32::RED);
+                        let painter = ui.painter();
+                        painter.line_segment(
+                            [pos2(rect.min.x, pos.y), pos2(rect.max.x, pos.y)],
+                            stroke,
+                        );
+                        painter.line_segment(
+                            [pos2(pos.x, rect.min.y), pos2(pos.x, rect.max.y)],
+                            stroke,
+                        );
+
+                        // Bit 0 is the first bitplane and bit 1 the second
+                        let value = emu.cpu.gfx.get(col, row) as u8
+                            | (emu.cpu.gfx2.get(col, row) as u8) << 1;
+                        let text = format!("(x: {}, y: {}) {:02X}", col, row, value);
+                        egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("gfx_pixel"), |ui| {
+                            ui.label(&text);
+                        });
+                        ui.label(text);
                     }
-                });
+                    None => {
+                        ui.label("(x: -, y: -)");
+                    }
+                }
             });
     }
 }
@@ -203,7 +2137,32 @@ impl Framework {
         }
     }
 
-    pub(crate) fn handle_events(&mut self, event: &winit::event::WindowEvent) {
+    pub(crate) fn apply_config(&mut self, config: &Config) {
+        self.gui.apply_config(config);
+    }
+
+    pub(crate) fn save_config(&mut self, emu: &Emu) {
+        self.gui.save_config(emu);
+    }
+
+    pub(crate) fn load_rom(&mut self, emu: &mut Emu, path: &Path) {
+        self.gui.load_rom(emu, path);
+    }
+
+    pub(crate) fn show_rom_browser(&mut self) {
+        self.gui.show_rom_browser = true;
+    }
+
+    pub(crate) fn quit_requested(&self) -> bool {
+        self.gui.quit_requested
+    }
+
+    pub(crate) fn show_error(&mut self, message: String) {
+        self.gui.error_dialog = Some(message);
+    }
+
+    pub(crate) fn handle_events(&mut self, event: &WindowEvent) {
+        self.gui.handle_event(event);
         self.egui_state.on_event(&self.egui_ctx, event);
     }
 
@@ -0,0 +1,21 @@
+// Only the emulator core builds for wasm32, the rest needs the desktop frontend's dependencies
+#[cfg(not(target_arch = "wasm32"))]
+pub mod audio;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod catalog;
+pub mod chip8;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod config;
+pub mod debugger;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod download;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod emu;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod replay;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod state;
+#[cfg(feature = "wasm32")]
+mod wasm;
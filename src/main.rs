@@ -1,91 +1,371 @@
-use std::time::Instant;
+use std::{
+    sync::mpsc::TryRecvError,
+    thread,
+    time::{Duration, Instant},
+};
 
+use cchipt::{
+    catalog,
+    chip8::{diff_roms, Chip8, CpuError, DiffKind, RomDiffEntry},
+    cli::Args,
+    config::Config,
+    download::{self, DownloadEvent, DownloadedRom},
+    emu::{Emu, FrameTimer, REFRESH_RATE},
+    replay::{ReplayPlayer, ReplayRecorder},
+};
+use clap::Parser;
 use color_eyre::{eyre::eyre, Result};
-use emu::{Emu, KEYS, REFRESH_RATE, WINDOW_HEIGHT, WINDOW_WIDTH};
 use gui::Framework;
 use pixels::{Pixels, SurfaceTexture};
 use winit::{
     dpi::LogicalSize,
-    event::Event,
+    event::{Event, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    platform::windows::WindowBuilderExtWindows,
-    window::{Theme, WindowBuilder},
+    window::{Fullscreen, WindowBuilder},
 };
 use winit_input_helper::WinitInputHelper;
 
-mod chip8;
-mod emu;
 mod gui;
 
+// Borderless on the current monitor, so the desktop resolution is kept
+fn fullscreen_mode(fullscreen: bool) -> Option<Fullscreen> {
+    fullscreen.then(|| Fullscreen::Borderless(None))
+}
+
+fn print_rom_diff(path_a: &str, path_b: &str) -> Result<()> {
+    let rom_a = std::fs::read(path_a)?;
+    let rom_b = std::fs::read(path_b)?;
+
+    println!("--- {}", path_a);
+    println!("+++ {}", path_b);
+    for entry in diff_roms(&rom_a, &rom_b) {
+        let removed = |entry: &RomDiffEntry| {
+            println!(
+                "-{:04X}: {:04X}  {}",
+                entry.address,
+                entry.opcode_a.unwrap_or_default(),
+                entry.mnemonic_a
+            )
+        };
+        let added = |entry: &RomDiffEntry| {
+            println!(
+                "+{:04X}: {:04X}  {}",
+                entry.address,
+                entry.opcode_b.unwrap_or_default(),
+                entry.mnemonic_b
+            )
+        };
+
+        match entry.kind() {
+            DiffKind::Same => println!(
+                " {:04X}: {:04X}  {}",
+                entry.address,
+                entry.opcode_a.unwrap_or_default(),
+                entry.mnemonic_a
+            ),
+            DiffKind::Changed => {
+                removed(&entry);
+                added(&entry);
+            }
+            DiffKind::Removed => removed(&entry),
+            DiffKind::Added => added(&entry),
+        }
+    }
+    Ok(())
+}
+
+fn load_downloaded_rom(emu: &mut Emu, rom: DownloadedRom) -> Result<()> {
+    emu.load_rom_bytes(rom.bytes)?;
+    emu.rom_name = rom.name;
+    Ok(())
+}
+
+// Shown in the title bar while a --url ROM is downloading
+fn download_title(received: usize, total: Option<u64>) -> String {
+    match total {
+        Some(total) if total > 0 => format!(
+            "cchipt - Downloading ROM… {}%",
+            received as u64 * 100 / total
+        ),
+        _ => format!("cchipt - Downloading ROM… {} bytes", received),
+    }
+}
+
+// One instruction per line, e.g. `0x0200: 6001  LD   V0, 01`
+fn print_disassembly(path: &str) -> Result<()> {
+    let rom = std::fs::read(path)?;
+    let cpu = Chip8::try_from(rom.as_slice())?;
+    for (address, opcode, mnemonic) in cpu.iter_instructions(0x200).take(rom.len().div_ceil(2)) {
+        println!("0x{:04X}: {:04X}  {}", address, opcode, mnemonic);
+    }
+    Ok(())
+}
+
+// Runs the ROM for a fixed number of instructions and prints the display, one character per pixel
+fn run_headless(emu: &mut Emu, cycles: u64) -> Result<()> {
+    let result = emu.run_headless(cycles);
+    for y in 0..result.gfx.height() {
+        let row: String = (0..result.gfx.width())
+            .map(|x| if result.gfx.get(x, y) { '#' } else { '.' })
+            .collect();
+        println!("{}", row);
+    }
+
+    // Returning the error exits with a non-zero status, a program that ran 00FD finished normally
+    match result.halted {
+        Some(CpuError::Exited(_)) | None => Ok(()),
+        Some(error) => Err(eyre!(
+            "CPU halted after {} instructions: {}",
+            result.cycles,
+            error
+        )),
+    }
+}
+
 fn main() -> Result<()> {
+    let args = Args::parse();
+    if let Some(roms) = &args.diff {
+        return print_rom_diff(&roms[0], &roms[1]);
+    }
+    if let Some(path) = &args.disassemble {
+        return print_disassembly(path);
+    }
+
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("Failed to load config: {}", e);
+        Config::new()
+    });
+
+    let mut emu = Emu::default();
+    emu.apply_config(&config);
+    emu.apply_args(&args)?;
+
+    if args.headless {
+        if let Some(url) = &args.url {
+            load_downloaded_rom(&mut emu, download::fetch_rom(url, |_, _| ())?)?;
+        } else if args.rom().is_none() {
+            return Err(eyre!("--headless needs a ROM to run"));
+        }
+        return run_headless(&mut emu, args.cycles);
+    }
+
+    thread::spawn(|| {
+        if let Err(e) = catalog::download_if_missing() {
+            eprintln!("Failed to download the ROM catalog: {}", e);
+        }
+    });
+
+    // RND is seeded from the replay, so it picks the same numbers as when it was recorded
+    let player = args.replay.as_ref().map(ReplayPlayer::read).transpose()?;
+    if let Some(player) = &player {
+        emu.cpu.seed_rng(player.seed);
+    }
+    let mut recorder = match &args.record {
+        Some(path) => {
+            let seed = args.seed.unwrap_or_else(rand::random);
+            emu.cpu.seed_rng(seed);
+            Some(ReplayRecorder::create(path, seed)?)
+        }
+        None => None,
+    };
+    let mut replay_frame = 0;
+
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
 
-    let window = WindowBuilder::new()
-        .with_theme(Some(Theme::Dark))
+    let (window_width, window_height) = (emu.window_width(), emu.window_height());
+    let window_builder = WindowBuilder::new()
         .with_title("cchipt")
-        .with_inner_size(LogicalSize::new(WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64))
-        .with_min_inner_size(LogicalSize::new(WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64))
+        .with_inner_size(LogicalSize::new(window_width as f64, window_height as f64))
+        .with_min_inner_size(LogicalSize::new(window_width as f64, window_height as f64))
         .with_maximized(true)
-        .build(&event_loop)?;
+        .with_fullscreen(fullscreen_mode(emu.fullscreen));
+    // The theme can only be set when building the window on Windows
+    #[cfg(target_os = "windows")]
+    let window_builder = {
+        use winit::{platform::windows::WindowBuilderExtWindows, window::Theme};
+        window_builder.with_theme(Some(Theme::Dark))
+    };
+    let window = window_builder.build(&event_loop)?;
 
     let (mut pixels, mut framework) = {
         let window_size = window.inner_size();
         let scale_factor = window.scale_factor() as f32;
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        let pixels = Pixels::new(WINDOW_WIDTH, WINDOW_HEIGHT, surface_texture)?;
+        let pixels = Pixels::new(window_width, window_height, surface_texture)?;
         let framework =
             Framework::new(window_size.width, window_size.height, scale_factor, &pixels);
         (pixels, framework)
     };
 
-    let mut emu = Emu::default();
-    emu.load_rom(&std::env::args().nth(1).unwrap())?;
+    framework.apply_config(&config);
+    // Without a ROM there is nothing to run, so start by picking one
+    if emu.rom.is_empty() && args.url.is_none() {
+        framework.show_rom_browser();
+    }
+    let mut download = args.url.clone().map(|url| {
+        window.set_title(&download_title(0, None));
+        download::spawn(url)
+    });
+
+    let mut skipped_frames = 0;
+    let mut frame_timer = FrameTimer::new();
+    let mut resized = false;
+    let mut last_frame_start: Option<Instant> = None;
+    let frame_duration = Duration::from_secs(1) / REFRESH_RATE as u32;
+    let mut next_frame = Instant::now();
 
     event_loop.run(move |event, _, control_flow| {
         let frame_start_time = Instant::now();
+        if let Some(receiver) = &download {
+            loop {
+                match receiver.try_recv() {
+                    Ok(DownloadEvent::Progress { received, total }) => {
+                        window.set_title(&download_title(received, total));
+                    }
+                    Ok(DownloadEvent::Done(result)) => {
+                        if let Err(e) = result.and_then(|rom| load_downloaded_rom(&mut emu, rom)) {
+                            framework.show_error(format!("Failed to download ROM: {}", e));
+                        }
+                        window.set_title("cchipt");
+                        download = None;
+                        break;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        window.set_title("cchipt");
+                        download = None;
+                        break;
+                    }
+                }
+            }
+        }
+        emu.check_host_capacity();
+        emu.update_effective_clock_rate();
+        // Input and window events wake the loop more often than the display refreshes, so the
+        // program only runs once the next frame is due
+        let frame_due = matches!(event, Event::MainEventsCleared) && frame_start_time >= next_frame;
         if input.update(&event) {
-            if input.quit() {
+            if input.quit() || framework.quit_requested() {
+                framework.save_config(&emu);
+                if let Some(recorder) = &mut recorder {
+                    if let Err(e) = recorder.flush() {
+                        eprintln!("Failed to write replay: {}", e);
+                    }
+                }
                 *control_flow = ControlFlow::Exit;
                 return;
             }
             if let Some(scale_factor) = input.scale_factor() {
                 framework.scale_factor(scale_factor);
             }
+            let mut rescaled = false;
             if let Some(size) = input.window_resized() {
                 pixels.resize_surface(size.width, size.height);
                 framework.resize(size.width, size.height);
+                resized = true;
+                rescaled = true;
+            }
+            if input.key_pressed(VirtualKeyCode::F5) {
+                emu.presentation_mode = !emu.presentation_mode;
+                resized = true;
+                rescaled = true;
+            }
+            // The buffer follows the display scale, which fills the window in presentation mode
+            if rescaled {
+                let size = window.inner_size();
+                emu.fit_presentation_scale(size.width, size.height);
+                pixels.resize_buffer(emu.window_width(), emu.window_height());
             }
 
-            let mut new_keystate = [false; 16];
-            for (i, key) in KEYS.iter().enumerate() {
-                new_keystate[i] = input.key_pressed(*key);
+            let mut held = [false; 16];
+            let mut pressed = [false; 16];
+            for (i, key) in emu.key_map.iter().enumerate() {
+                held[i] = input.key_held(*key);
+                pressed[i] = input.key_pressed(*key);
+            }
+            emu.update_keystates(held, pressed);
+            emu.boosted = input.key_held(emu.boost_key);
+
+            if input.key_pressed(VirtualKeyCode::F3) {
+                emu.show_fps = !emu.show_fps;
+                emu.cpu.gfx_dirty = true;
+            }
+            // The display keeps its aspect ratio, pixels scales it to fit and centres it
+            if input.key_pressed(VirtualKeyCode::F11)
+                || input.held_alt() && input.key_pressed(VirtualKeyCode::Return)
+            {
+                emu.fullscreen = !emu.fullscreen;
+                window.set_fullscreen(fullscreen_mode(emu.fullscreen));
+            }
+            // Steps run outside of any frame, so a replay could not reproduce them
+            let replaying = recorder.is_some() || player.is_some();
+            if emu.run_steps && !replaying && input.key_pressed(emu.step_key) {
+                if let Err(e) = emu.step() {
+                    framework.show_error(format!("CPU halted: {}", e));
+                }
             }
-            emu.update_keystates(new_keystate);
-
-            // if emu.run_steps {
-            //     if input.key_pressed(VirtualKeyCode::S) {
-            //         emu.progress();
-            //     }
-            // } else {
-            //     for _ in 0..(emu.clock_rate / REFRESH_RATE) {
-            //         emu.progress();
-            //     }
-            // }
         }
-        if !emu.run_steps {
-            for _ in 0..(emu.clock_rate / REFRESH_RATE) {
-                emu.progress();
+        if frame_due {
+            // Carried on from the last deadline so frames stay evenly spaced, unless it fell behind
+            next_frame = (next_frame + frame_duration).max(frame_start_time);
+            emu.cpu.vblank = true;
+            if !emu.run_steps {
+                // Input is recorded and replayed per emulated frame, so pausing does not shift it
+                if let Some(frame) = player
+                    .as_ref()
+                    .and_then(|player| player.frame(replay_frame))
+                {
+                    frame.apply(&mut emu);
+                }
+                if let Some(rec) = &mut recorder {
+                    if let Err(e) = rec.record(replay_frame, &emu) {
+                        eprintln!("Failed to record replay, recording stopped: {}", e);
+                        recorder = None;
+                    }
+                }
+                replay_frame += 1;
+
+                if let Err(e) = emu.run_frame(&mut frame_timer) {
+                    framework.show_error(format!("CPU halted: {}", e));
+                }
+                skipped_frames += 1;
             }
         }
-        window.request_redraw();
+        // While paused every step is shown, so the last frame before pausing is never skipped
+        if emu.run_steps || skipped_frames >= emu.frame_skip {
+            skipped_frames = 0;
+            window.request_redraw();
+        }
 
         match event {
             Event::WindowEvent { event, .. } => {
+                if let WindowEvent::DroppedFile(path) = &event {
+                    framework.load_rom(&mut emu, path);
+                }
                 framework.handle_events(&event);
             }
             Event::RedrawRequested(_) => {
-                emu.draw(pixels.get_frame());
+                // Time between rendered frames, only while running as a paused program redraws
+                // whenever the GUI changes
+                if emu.run_steps {
+                    last_frame_start = None;
+                } else {
+                    if let Some(last_start) = last_frame_start {
+                        let actual_ms =
+                            frame_start_time.duration_since(last_start).as_millis() as u64;
+                        emu.record_frame_timing(1000 / REFRESH_RATE, actual_ms);
+                    }
+                    last_frame_start = Some(frame_start_time);
+                }
+                emu.total_frames += 1;
+                emu.record_frame();
+                // The GUI still has to be rendered every frame, only the display is left as is.
+                // The FPS overlay and a ghost trail change all the time, so they are always redrawn
+                if emu.cpu.gfx_dirty || resized || emu.show_fps || emu.ghost_frames > 0 {
+                    emu.draw(pixels.get_frame());
+                    resized = false;
+                }
                 framework.prepare(&window, &mut emu);
                 let render_result = pixels.render_with(|encoder, render_target, context| {
                     context.scaling_renderer.render(encoder, render_target);
@@ -102,12 +382,6 @@ fn main() -> Result<()> {
             _ => (),
         }
 
-        let elapsed_time = Instant::now().duration_since(frame_start_time).as_millis() as u64;
-        let wait_millis = match 1000 / REFRESH_RATE >= elapsed_time {
-            true => 1000 / REFRESH_RATE - elapsed_time,
-            false => 0,
-        };
-        let new_inst = frame_start_time + std::time::Duration::from_millis(wait_millis);
-        *control_flow = ControlFlow::WaitUntil(new_inst);
+        *control_flow = ControlFlow::WaitUntil(next_frame);
     });
 }
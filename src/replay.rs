@@ -0,0 +1,177 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::emu::Emu;
+
+// Every replay file starts with the magic bytes followed by the format version and the RND
+// seed as a little endian u64, then one bincode encoded `ReplayFrame` per emulated frame
+pub const REPLAY_MAGIC: &[u8; 6] = b"CCHIPR";
+pub const REPLAY_VERSION: u8 = 2;
+
+// Input seen by the emulator during one frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub frame: u64,
+    pub held: [bool; 16],
+    pub pressed: [bool; 16],
+    pub boosted: bool, // Boosted frames run more instructions
+}
+
+impl ReplayFrame {
+    // Replaces the live input with the recorded one
+    pub fn apply(&self, emu: &mut Emu) {
+        emu.update_keystates(self.held, self.pressed);
+        emu.boosted = self.boosted;
+    }
+}
+
+// Frames are written as they are recorded, so a crash only loses what is still buffered
+pub struct ReplayRecorder {
+    writer: BufWriter<File>,
+}
+
+impl ReplayRecorder {
+    // The emulator has to be seeded with `seed` too, so RND picks the same numbers on replay
+    pub fn create(path: impl AsRef<Path>, seed: u64) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(REPLAY_MAGIC)?;
+        writer.write_all(&[REPLAY_VERSION])?;
+        writer.write_all(&seed.to_le_bytes())?;
+        Ok(Self { writer })
+    }
+
+    // Input the emulator is about to run the frame with
+    pub fn record(&mut self, frame: u64, emu: &Emu) -> Result<()> {
+        let frame = ReplayFrame {
+            frame,
+            held: emu.cpu.key_states,
+            pressed: emu.cpu.key_presses,
+            boosted: emu.boosted,
+        };
+        bincode::serialize_into(&mut self.writer, &frame)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub struct ReplayPlayer {
+    pub seed: u64,
+    pub frames: Vec<ReplayFrame>, // Ordered by frame index
+}
+
+impl ReplayPlayer {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = fs::read(path)?;
+        let body = bytes
+            .strip_prefix(REPLAY_MAGIC.as_slice())
+            .ok_or_else(|| eyre!("not a cchipt replay"))?;
+        let body = match body.split_first() {
+            Some((&REPLAY_VERSION, body)) => body,
+            Some((version, _)) => {
+                return Err(eyre!(
+                    "replay version {} is not supported, expected {}",
+                    version,
+                    REPLAY_VERSION
+                ))
+            }
+            None => return Err(eyre!("replay is empty")),
+        };
+        let (seed, mut body) = body
+            .split_first_chunk::<8>()
+            .ok_or_else(|| eyre!("replay is missing its seed"))?;
+        let seed = u64::from_le_bytes(*seed);
+
+        let mut frames = Vec::new();
+        while !body.is_empty() {
+            frames.push(bincode::deserialize_from(&mut body)?);
+        }
+        Ok(Self { seed, frames })
+    }
+
+    // Input recorded for a frame, `None` once the replay has run out
+    pub fn frame(&self, frame: u64) -> Option<&ReplayFrame> {
+        self.frames
+            .binary_search_by_key(&frame, |recorded| recorded.frame)
+            .ok()
+            .map(|i| &self.frames[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::Sound,
+        chip8::Chip8Snapshot,
+        emu::{FrameTimer, BOOST_FACTOR},
+    };
+
+    #[rustfmt::skip]
+    const ROM: [u8; 14] = [
+        0xC0, 0xFF, // RND  V0, ff
+        0x81, 0x04, // ADD  V1, V0
+        0xF2, 0x0A, // LD   V2, K
+        0x83, 0x24, // ADD  V3, V2
+        0xE2, 0x9E, // SKP  V2
+        0x74, 0x01, // ADD  V4, 01
+        0x12, 0x00, // JP   200
+    ];
+
+    fn emu(seed: u64) -> Emu {
+        let mut emu = Emu::with_sound(Sound::silent());
+        emu.load_rom_bytes(ROM.to_vec()).unwrap();
+        emu.clock_rate = 600;
+        emu.cpu.seed_rng(seed);
+        emu.run_steps = false;
+        emu
+    }
+
+    #[test]
+    fn replay_matches_recording() {
+        let path = std::env::temp_dir().join(format!("cchipt-replay-{}", std::process::id()));
+        let frames = 120;
+
+        let mut recording = emu(0x5EED);
+        let mut recorder = ReplayRecorder::create(&path, 0x5EED).unwrap();
+        let mut frame_timer = FrameTimer::new();
+        for frame in 0..frames {
+            // Key (frame % 16) pressed every third frame, boost held for a stretch
+            let mut pressed = [false; 16];
+            pressed[frame as usize % 16] = frame % 3 == 0;
+            recording.update_keystates(pressed, pressed);
+            recording.boosted = (40..60).contains(&frame);
+
+            recorder.record(frame, &recording).unwrap();
+            recording.run_frame(&mut frame_timer).unwrap();
+        }
+        recorder.flush().unwrap();
+
+        let player = ReplayPlayer::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(player.seed, 0x5EED);
+
+        let mut replay = emu(player.seed);
+        let mut frame_timer = FrameTimer::new();
+        for frame in 0..frames {
+            player.frame(frame).unwrap().apply(&mut replay);
+            replay.run_frame(&mut frame_timer).unwrap();
+        }
+
+        assert_eq!(
+            Chip8Snapshot::from(&replay.cpu),
+            Chip8Snapshot::from(&recording.cpu)
+        );
+        // 100 normal frames at 10 instructions and 20 boosted ones
+        assert_eq!(replay.total_cycles, 100 * 10 + 20 * 10 * BOOST_FACTOR);
+    }
+}
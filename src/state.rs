@@ -0,0 +1,128 @@
+use std::{fs, path::Path};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::chip8::{Chip8, Chip8Snapshot, Framebuffer, Mode, Quirks};
+
+// Every save state file starts with the magic bytes followed by the format version
+pub const STATE_MAGIC: &[u8; 6] = b"CCHIPT";
+pub const STATE_VERSION: u8 = 1;
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmuState {
+    pub V: [u8; 16],
+    pub I: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub stack: [u16; 16],
+    pub sp: u16,
+    pub pc: u16,
+    pub memory: Vec<u8>, // serde only handles arrays of up to 32 elements
+    pub key_states: [bool; 16],
+    pub gfx: Framebuffer,
+    pub gfx2: Framebuffer,
+    pub plane_mask: u8,
+    pub quirks: Quirks,
+    pub mode: Mode,
+    pub rpl_flags: [u8; 8],
+    pub audio_buffer: [u8; 16],
+    pub audio_pitch: u8,
+    pub clock_rate: u64,
+}
+
+impl EmuState {
+    pub fn new(cpu: &Chip8, clock_rate: u64) -> Self {
+        Self {
+            V: cpu.V,
+            I: cpu.I,
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            stack: cpu.stack,
+            sp: cpu.sp,
+            pc: cpu.pc,
+            memory: cpu.memory.to_vec(),
+            key_states: cpu.key_states,
+            gfx: cpu.gfx.clone(),
+            gfx2: cpu.gfx2.clone(),
+            plane_mask: cpu.plane_mask,
+            quirks: cpu.quirks,
+            mode: cpu.mode,
+            rpl_flags: cpu.rpl_flags,
+            audio_buffer: cpu.audio_buffer,
+            audio_pitch: cpu.audio_pitch,
+            clock_rate,
+        }
+    }
+
+    pub fn to_cpu(&self) -> Result<Chip8> {
+        let mut cpu = Chip8::new();
+        self.restore_into(&mut cpu)?;
+        Ok(cpu)
+    }
+
+    // Keeps the font and RNG of `cpu`. The mode and quirks are saved along with the state, so
+    // they are restored too
+    pub fn restore_into(&self, cpu: &mut Chip8) -> Result<()> {
+        for plane in [&self.gfx, &self.gfx2] {
+            if plane.pixels().len() != plane.width() * plane.height() {
+                return Err(eyre!("save state has a malformed display"));
+            }
+        }
+
+        let memory = self.memory.clone().into_boxed_slice();
+        let memory_len = memory.len();
+        let snapshot = Chip8Snapshot {
+            v: self.V,
+            i: self.I,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            stack: self.stack,
+            sp: self.sp,
+            pc: self.pc,
+            memory: memory.try_into().map_err(|_| {
+                eyre!(
+                    "save state has {} bytes of memory, expected 4096",
+                    memory_len
+                )
+            })?,
+            key_states: self.key_states,
+            gfx: self.gfx.clone(),
+            gfx2: self.gfx2.clone(),
+            plane_mask: self.plane_mask,
+            rpl_flags: self.rpl_flags,
+            audio_buffer: self.audio_buffer,
+            audio_pitch: self.audio_pitch,
+        };
+
+        cpu.quirks = self.quirks;
+        cpu.mode = self.mode;
+        cpu.restore(snapshot);
+        Ok(())
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut bytes = STATE_MAGIC.to_vec();
+        bytes.push(STATE_VERSION);
+        bytes.extend(bincode::serialize(self)?);
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = fs::read(path)?;
+        let body = bytes
+            .strip_prefix(STATE_MAGIC.as_slice())
+            .ok_or_else(|| eyre!("not a cchipt save state"))?;
+        match body.split_first() {
+            Some((&STATE_VERSION, body)) => Ok(bincode::deserialize(body)?),
+            Some((version, _)) => Err(eyre!(
+                "save state version {} is not supported, expected {}",
+                version,
+                STATE_VERSION
+            )),
+            None => Err(eyre!("save state is empty")),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use wasm_bindgen::prelude::*;
+
+use crate::chip8::Chip8;
+
+// Exposed to JavaScript as `Chip8`
+#[wasm_bindgen(js_name = Chip8)]
+pub struct WasmChip8 {
+    cpu: Chip8,
+}
+
+#[wasm_bindgen(js_class = Chip8)]
+impl WasmChip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self { cpu: Chip8::new() }
+    }
+
+    pub fn load_rom(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        self.cpu = Chip8::try_from(bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(())
+    }
+
+    pub fn tick(&mut self) -> Result<(), JsValue> {
+        self.cpu
+            .tick()
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    // Call at 60 Hz, separately from `tick`
+    pub fn tick_timers(&mut self) {
+        self.cpu.tick_timers();
+    }
+
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if let Some(state) = self.cpu.key_states.get_mut(key as usize) {
+            if pressed && !*state {
+                self.cpu.key_presses[key as usize] = true;
+            }
+            *state = pressed;
+        }
+    }
+
+    // Row-major pixels, one byte each, 1 if the pixel is on
+    pub fn get_display(&self) -> Vec<u8> {
+        self.cpu.gfx.pixels().iter().map(|on| *on as u8).collect()
+    }
+
+    pub fn display_width(&self) -> usize {
+        self.cpu.gfx.width()
+    }
+
+    pub fn display_height(&self) -> usize {
+        self.cpu.gfx.height()
+    }
+
+    // Whether the buzzer should be sounding right now
+    pub fn sound_active(&self) -> bool {
+        self.cpu.sound_active
+    }
+
+    // Returns whether a beep is due and clears the flag
+    pub fn make_beep(&mut self) -> bool {
+        std::mem::take(&mut self.cpu.make_beep)
+    }
+}
+
+impl Default for WasmChip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}